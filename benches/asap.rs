@@ -0,0 +1,73 @@
+// The crate is binary-only, so pull the solver in by path
+#[allow(dead_code, unused_imports)]
+#[path = "../src/asap_cpu.rs"]
+mod asap_cpu;
+
+use asap_cpu::ASAP;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const N_TASKS: usize = 100;
+const N_CMPS: usize = 1000;
+
+fn random_cmps() -> Vec<[usize; 2]> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..N_CMPS)
+        .map(|_| {
+            let a = rng.gen_range(0..N_TASKS);
+            let b = (a + rng.gen_range(1..N_TASKS)) % N_TASKS;
+            [a, b]
+        })
+        .collect()
+}
+
+fn bench_add_comparisons(c: &mut Criterion) {
+    let cmps = random_cmps();
+    let mut group = c.benchmark_group("add_1000_comparisons");
+    group.sample_size(10);
+
+    group.bench_function("one_at_a_time", |b| {
+        b.iter(|| {
+            let mut asap = ASAP::new(N_TASKS);
+            for cmp in &cmps {
+                black_box(asap.add_comparison_batch(std::slice::from_ref(cmp)));
+            }
+        })
+    });
+
+    group.bench_function("batch", |b| {
+        b.iter(|| {
+            let mut asap = ASAP::new(N_TASKS);
+            black_box(asap.add_comparison_batch(&cmps));
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_optimal_pair(c: &mut Criterion) {
+    const N: usize = 500;
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut m = vec![vec![0; N]; N];
+    for _ in 0..2 * N {
+        let a = rng.gen_range(0..N);
+        let b = (a + rng.gen_range(1..N)) % N;
+        // lower index tends to win, so the ratings spread out
+        m[a.min(b)][a.max(b)] += 1;
+    }
+    let mut asap = ASAP::new(N);
+    let (prob, ms, vs) = asap.run_solver(&m);
+
+    let mut group = c.benchmark_group("optimal_pair_500_tasks");
+    group.sample_size(10);
+    group.bench_function("all_pairs", |b| {
+        b.iter(|| black_box(asap.optimal_pair_in_window(&prob, &ms, &vs, None)))
+    });
+    group.bench_function("window_3", |b| {
+        b.iter(|| black_box(asap.optimal_pair_in_window(&prob, &ms, &vs, Some(3))))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_add_comparisons, bench_optimal_pair);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+// The crate is binary-only, so pull the graph code in by path
+#[allow(dead_code, unused_imports)]
+#[path = "../src/plot_ratings.rs"]
+mod plot_ratings;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use plot_ratings::{transitive_reduction, transitive_reduction_petgraph};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+
+// Edges like the ones plot_ratings draws: a random set of compared pairs,
+// weighted by the probability that `from` beats `to` under random ratings.
+// Both the p > 0.5 and p < 0.5 subgraphs are then acyclic.
+fn random_dag(n: usize) -> HashMap<(usize, usize), f64> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let ratings: Vec<f64> = (0..n).map(|_| rng.gen_range(-3.0..3.0)).collect();
+    let mut edges = HashMap::new();
+    for _ in 0..n * 5 {
+        let a = rng.gen_range(0..n);
+        let b = (a + rng.gen_range(1..n)) % n;
+        let p = 1.0 / (1.0 + (ratings[b] - ratings[a]).exp());
+        edges.insert((a, b), p);
+    }
+    edges
+}
+
+fn bench_transitive_reduction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transitive_reduction");
+    for n in [10, 50, 200] {
+        let edges = random_dag(n);
+        group.bench_with_input(BenchmarkId::new("dfs", n), &edges, |b, edges| {
+            b.iter(|| {
+                let mut edges = edges.clone();
+                transitive_reduction(&mut edges, &|p| *p > 0.5);
+                transitive_reduction(&mut edges, &|p| *p < 0.5);
+                black_box(edges)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("petgraph", n), &edges, |b, edges| {
+            b.iter(|| {
+                let mut edges = edges.clone();
+                transitive_reduction_petgraph(&mut edges, &|p| *p > 0.5);
+                transitive_reduction_petgraph(&mut edges, &|p| *p < 0.5);
+                black_box(edges)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_transitive_reduction);
+criterion_main!(benches);
@@ -1,5 +1,8 @@
 use libm::{erf, erfc, exp};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::f64::consts::PI;
+use std::time::{Duration, Instant};
 
 // perf ideas:
 // - use selective EIG a la (https://arxiv.org/abs/2004.05691) (~only eval posterior on pairs with closeish ratings)
@@ -8,25 +11,333 @@ use std::f64::consts::PI;
 //   - doable with autodiff i think, just backprop on KL div & use gradients of posteriors as thresholds
 // - prio queue for updates in message passing by magnitude of update?
 
+/// Knobs for the TrueSkill model.
+#[derive(Clone, Copy, Debug)]
+pub struct TrueSkillConfig {
+    /// Performance variance (beta^2) of a single comparison
+    pub beta_sq: f64,
+    /// Extra performance variance for comparisons involving rarely compared
+    /// items, divided by the smaller of the two items' comparison counts, so a
+    /// single comparison can't give a deceptively tight interval
+    pub rare_item_beta_sq: f64,
+    /// Precision of the zero-mean prior every item starts from
+    pub prior_precision: f64,
+    /// Solve a single added comparison by propagating outwards from its two
+    /// items, stopping where the changes fall below the convergence
+    /// threshold, instead of revisiting every comparison. Much cheaper on
+    /// large lists, at the cost of small differences from a full solve.
+    pub incremental_updates: bool,
+}
+
+impl Default for TrueSkillConfig {
+    fn default() -> Self {
+        TrueSkillConfig {
+            beta_sq: 1.0,
+            rare_item_beta_sq: 0.0,
+            prior_precision: 0.02,
+            incremental_updates: false,
+        }
+    }
+}
+
+// Lists up to this size get the exhaustive expected information gain search;
+// larger ones only consider each item's EIG_SEARCH_WINDOW nearest neighbours
+const FULL_EIG_SEARCH_MAX_ITEMS: usize = 30;
+const EIG_SEARCH_WINDOW: usize = 3;
+// z-score of the band in `pair_probability`, ~95% of the skill difference
+const PROBABILITY_BAND_Z: f64 = 1.96;
+
 pub struct ASAP {
     ts_solver: TrueSkillSolver,
+    // wall-clock time of the last full solve
+    solve_time: Duration,
+    // draw margins set for comparisons that haven't been added yet, by id
+    pending_margins: HashMap<usize, f64>,
 }
 
 impl ASAP {
     pub fn new(n: usize) -> Self {
+        Self::with_config(n, TrueSkillConfig::default())
+    }
+
+    pub fn with_config(n: usize, config: TrueSkillConfig) -> Self {
         ASAP {
-            ts_solver: TrueSkillSolver::new(n),
+            ts_solver: TrueSkillSolver::new(n, config),
+            solve_time: Duration::ZERO,
+            pending_margins: HashMap::new(),
         }
     }
 
-    pub fn run_asap(
-        &mut self,
-        m: &[Vec<i32>],
-    ) -> ((usize, usize), Vec<Vec<f64>>, Vec<f64>, Vec<f64>) {
-        let n = m.len();
+    /// Use `margin` instead of the global 0 as the draw margin for comparison
+    /// `comparison_id`, i.e. the one added in that position. It can be set
+    /// before the comparison is added. A larger margin means the win says
+    /// more about the difference between the two items. Margins belong to
+    /// comparisons, not pairs: to give a pair a margin, set it on each of
+    /// that pair's comparisons.
+    pub fn set_draw_margin(&mut self, comparison_id: usize, margin: f64) {
+        if comparison_id < self.ts_solver.g.len() {
+            self.ts_solver.set_draw_margin(comparison_id, margin);
+        } else {
+            self.pending_margins.insert(comparison_id, margin);
+        }
+    }
+
+    // Hand over the pending margins of the comparisons added from `first_id` on
+    fn apply_pending_margins(&mut self, first_id: usize) {
+        if self.pending_margins.is_empty() {
+            return;
+        }
+        for id in first_id..self.ts_solver.g.len() {
+            if let Some(margin) = self.pending_margins.remove(&id) {
+                self.ts_solver.set_draw_margin(id, margin);
+            }
+        }
+    }
+
+    /// Message passing iterations used by the last full solve.
+    pub fn solver_iterations(&self) -> usize {
+        self.ts_solver.iterations
+    }
+
+    /// Wall-clock time of the last full solve.
+    pub fn solve_time(&self) -> Duration {
+        self.solve_time
+    }
+
+    /// Solve for the current ratings only, skipping the expected information
+    /// gain search (which costs a solver pass per candidate pair).
+    pub fn run_solver(&mut self, m: &[Vec<i32>]) -> (Vec<Vec<f64>>, Vec<f64>, Vec<f64>) {
         let g = self.unroll_mat(m);
+        self.run_solver_on(&g)
+    }
+
+    /// `run_solver` for a list of `[winner, loser]` comparisons rather than a
+    /// win matrix, which keeps their order (see `rank_stability_score`).
+    pub fn run_solver_on(&mut self, cmps: &[[usize; 2]]) -> (Vec<Vec<f64>>, Vec<f64>, Vec<f64>) {
+        let (ms_curr, vs_curr) = self.add_comparison_batch(cmps);
+        let prob = self.compute_prob_cmps();
+        (prob, ms_curr, vs_curr)
+    }
+
+    /// Average rank displacement caused by each of the last `window`
+    /// comparisons, in the order they were added: for each one, the sum of
+    /// |rank before - rank after| over all items, divided by the number of
+    /// items. Close to 0 means more comparisons are unlikely to change much.
+    /// Re-solves `window + 1` times.
+    pub fn rank_stability_score(&self, window: usize) -> f64 {
+        let n = self.ts_solver.n;
+        let g = &self.ts_solver.g;
+        let window = window.min(g.len());
+        if window == 0 || n == 0 {
+            return 0.0;
+        }
+
+        let mut replay = TrueSkillSolver::new(n, self.ts_solver.config);
+        for (&p, &score) in &self.ts_solver.anchors {
+            replay.set_anchor(p, score);
+        }
+        for (&p, &mean) in &self.ts_solver.prior_means {
+            replay.set_prior_mean(p, mean);
+        }
+        let push = |replay: &mut TrueSkillSolver, id: usize| {
+            replay.push_cmp(g[id][0], g[id][1]);
+            if let Some(margins) = &self.ts_solver.draw_margins {
+                replay.set_draw_margin(id, margins[id]);
+            }
+        };
+
+        for id in 0..g.len() - window {
+            push(&mut replay, id);
+        }
+        let mut before = ranks(&replay.solve(true).0);
+        let mut displacement = 0.0;
+        for id in g.len() - window..g.len() {
+            push(&mut replay, id);
+            let after = if self.ts_solver.config.incremental_updates {
+                ranks(&replay.solve_last().0)
+            } else {
+                ranks(&replay.solve(true).0)
+            };
+            let moved: usize = before.iter().zip(&after).map(|(&b, &a)| b.abs_diff(a)).sum();
+            displacement += moved as f64 / n as f64;
+            before = after;
+        }
+        displacement / window as f64
+    }
+
+    /// How far the last solve has come towards `target_variance`, as a
+    /// percentage: for each item, the share of the way its variance has moved
+    /// from the prior variance down to the target, clamped to [0, 1] and
+    /// averaged over all items except anchors. 100 means every item is at or
+    /// below the target.
+    pub fn percent_confident(&self, target_variance: f64) -> f64 {
+        let vs: Vec<f64> = (0..self.ts_solver.n)
+            .filter(|p| !self.ts_solver.anchors.contains_key(p))
+            .map(|p| self.ts_solver.vs[p])
+            .collect();
+        if vs.is_empty() {
+            return 0.0;
+        }
+        let initial_variance = 1.0 / self.ts_solver.config.prior_precision;
+        let span = (initial_variance - target_variance).max(f64::EPSILON);
+        let progress: f64 = vs
+            .iter()
+            .map(|&v| ((initial_variance - v) / span).clamp(0.0, 1.0))
+            .sum();
+        100.0 * progress / vs.len() as f64
+    }
+
+    /// Pin item `i` at `score` as a fixed reference point: it takes part in
+    /// comparisons like any other item, but the solver never moves its
+    /// rating. Comparing against a few anchors puts the other items on a
+    /// stable, absolute scale rather than one relative to each other.
+    pub fn pin_anchor(&mut self, i: usize, score: f64) {
+        self.ts_solver.set_anchor(i, score);
+    }
+
+    /// Start items off from a known win rate in [0, 1], e.g. when migrating
+    /// from another ranking system, instead of from the middle. A rate maps
+    /// to the prior mean `ndtri(rate) * sqrt(prior variance)`, so an item
+    /// that won 84% of the time starts one standard deviation above average.
+    /// The prior is as weak as the default one and the next solve refines it
+    /// with the comparisons. Rates are clamped to [0.01, 0.99] to keep 0 and
+    /// 1 finite.
+    pub fn add_prior_from_win_rates(&mut self, win_rates: &HashMap<usize, f64>) {
+        let sd = (1.0 / self.ts_solver.config.prior_precision).sqrt();
+        for (&i, &rate) in win_rates {
+            self.set_prior(i, ndtri(rate.clamp(0.01, 0.99)) * sd);
+        }
+    }
+
+    /// Start item `i` from `mean` instead of 0, e.g. its score in an existing
+    /// ranking. Unlike `pin_anchor` this is only a starting point that
+    /// comparisons move as usual.
+    pub fn set_prior(&mut self, i: usize, mean: f64) {
+        self.ts_solver.set_prior_mean(i, mean);
+    }
+
+    /// Append `[winner, loser]` comparisons and re-solve once at the end,
+    /// instead of once per comparison. Returns the new means and variances.
+    pub fn add_comparison_batch(&mut self, cmps: &[[usize; 2]]) -> (Vec<f64>, Vec<f64>) {
+        if let [[winner, loser]] = cmps {
+            if self.ts_solver.config.incremental_updates {
+                return self.solve_incremental(*winner, *loser);
+            }
+        }
+        let first_new = self.ts_solver.g.len();
+        self.ts_solver.push_many(cmps);
+        self.apply_pending_margins(first_new);
+
+        let start = Instant::now();
+        let (ms_curr, vs_curr) = self.ts_solver.solve(true);
+        self.solve_time = start.elapsed();
+        (ms_curr, vs_curr)
+    }
+
+    /// Append one `winner` over `loser` comparison and update only what it
+    /// affects: messages spread from the two items and stop where the
+    /// posteriors move less than the convergence threshold. Roughly
+    /// O(degree * iterations) instead of a pass over every comparison.
+    /// Returns the new means and variances.
+    pub fn solve_incremental(&mut self, winner: usize, loser: usize) -> (Vec<f64>, Vec<f64>) {
+        self.ts_solver.push_cmp(winner, loser);
+        self.apply_pending_margins(self.ts_solver.g.len() - 1);
+
+        let start = Instant::now();
+        let (ms_curr, vs_curr) = self.ts_solver.solve_last();
+        self.solve_time = start.elapsed();
+        (ms_curr, vs_curr)
+    }
+
+    /// Cheap stand-in for the full EIG search on large lists: only considers
+    /// pairs that have never been compared directly and scores them by
+    /// p * (1 - p) * (v_i + v_j), i.e. how uncertain the outcome is weighted by
+    /// how much the two posteriors still have to move. Uses the ratings from
+    /// the last solve. O(n^2 + k log k).
+    ///
+    /// With a `window`, each item is only paired with the `window` items on
+    /// either side of it when sorted by mean, which makes it O(n log n) for
+    /// very large lists; the most uncertain outcomes are between close items
+    /// anyway.
+    pub fn most_informative_uncompared_pairs(
+        &self,
+        k: usize,
+        window: Option<usize>,
+    ) -> Vec<(usize, usize)> {
+        let (ms, vs) = (&self.ts_solver.ms, &self.ts_solver.vs);
+
+        let compared: HashSet<(usize, usize)> = self
+            .ts_solver
+            .g
+            .iter()
+            .map(|&[a, b]| (a.max(b), a.min(b)))
+            .collect();
+
+        let mut candidates = Vec::new();
+        for (i, j) in candidate_pairs(ms, window) {
+            if compared.contains(&(i, j)) {
+                continue;
+            }
+            let vars_sum = self.ts_solver.config.beta_sq + vs[i] + vs[j];
+            let p = ndtr((ms[i] - ms[j]) / vars_sum.sqrt());
+            candidates.push(((i, j), p * (1.0 - p) * (vs[i] + vs[j])));
+        }
+
+        let k = k.min(candidates.len());
+        if k == 0 {
+            return Vec::new();
+        }
+        let by_gain_desc = |a: &((usize, usize), f64), b: &((usize, usize), f64)| {
+            b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal)
+        };
+        candidates.select_nth_unstable_by(k - 1, by_gain_desc);
+        candidates.truncate(k);
+        candidates.sort_by(by_gain_desc);
+        candidates.into_iter().map(|(pair, _)| pair).collect()
+    }
 
-        self.compute_information_gain_mat(n, &g)
+    /// Rough comparison-sort estimate of the total comparisons needed: n log2 n.
+    pub fn heuristic_comparisons_bound(&self) -> usize {
+        let n = self.ts_solver.n as f64;
+        if n < 2.0 {
+            return 0;
+        }
+        (n * n.log2()).ceil() as usize
+    }
+
+    /// Total comparisons needed to bring every item down to `target_variance`,
+    /// following the ASAP paper's bound n * ln(n / target_variance) / (2 * I),
+    /// where I is the average Fisher information of a comparison between
+    /// neighbouring items (by current mean), which are the ones worth asking.
+    pub fn theoretical_comparisons_bound(&self, target_variance: f64) -> usize {
+        let n = self.ts_solver.n;
+        if n < 2 {
+            return 0;
+        }
+        let (ms, vs) = (&self.ts_solver.ms, &self.ts_solver.vs);
+
+        let mut sorted: Vec<usize> = (0..n).collect();
+        sorted.sort_by(|&a, &b| ms[b].partial_cmp(&ms[a]).unwrap_or(std::cmp::Ordering::Equal));
+
+        let info_per_comparison = sorted
+            .windows(2)
+            .map(|w| {
+                let vars_sum = self.ts_solver.config.beta_sq + vs[w[0]] + vs[w[1]];
+                let z = (ms[w[0]] - ms[w[1]]) / vars_sum.sqrt();
+                let p = ndtr(z);
+                let pdf = exp(-z * z / 2.0) / (2.0 * PI).sqrt();
+                pdf * pdf / (p * (1.0 - p)).max(f64::EPSILON) / vars_sum
+            })
+            .sum::<f64>()
+            / (n - 1) as f64;
+
+        let n = n as f64;
+        let bound = n * (n / target_variance).ln().max(0.0) / (2.0 * info_per_comparison);
+        if bound.is_finite() {
+            bound.ceil() as usize
+        } else {
+            usize::MAX
+        }
     }
 
     fn unroll_mat(&self, m: &[Vec<i32>]) -> Vec<[usize; 2]> {
@@ -56,7 +367,7 @@ impl ASAP {
                     prob[i][j] = 0.0;
                 } else {
                     let diff_means = means[i] - means[j];
-                    let vars_sum = 1.0 + vrs[i] + vrs[j];
+                    let vars_sum = self.ts_solver.config.beta_sq + vrs[i] + vrs[j];
                     prob[i][j] = ndtr(diff_means / vars_sum.sqrt());
                 }
             }
@@ -64,35 +375,115 @@ impl ASAP {
         prob
     }
 
-    fn compute_information_gain_mat(
+    /// Pick the pair with the highest expected information gain, given the
+    /// output of the last `run_solver`. Runs the solver once per candidate pair,
+    /// so above `FULL_EIG_SEARCH_MAX_ITEMS` items only each item's
+    /// `EIG_SEARCH_WINDOW` nearest neighbours by mean are candidates.
+    pub fn optimal_pair(
+        &mut self,
+        prob: &[Vec<f64>],
+        ms_curr: &[f64],
+        vs_curr: &[f64],
+    ) -> (usize, usize) {
+        let window = (ms_curr.len() > FULL_EIG_SEARCH_MAX_ITEMS).then_some(EIG_SEARCH_WINDOW);
+        self.optimal_pair_in_window(prob, ms_curr, vs_curr, window)
+    }
+
+    /// `optimal_pair` with an explicit candidate window, `None` searching all
+    /// pairs.
+    pub fn optimal_pair_in_window(
         &mut self,
-        n: usize,
-        g: &[[usize; 2]],
-    ) -> ((usize, usize), Vec<Vec<f64>>, Vec<f64>, Vec<f64>) {
+        prob: &[Vec<f64>],
+        ms_curr: &[f64],
+        vs_curr: &[f64],
+        window: Option<usize>,
+    ) -> (usize, usize) {
+        let n = ms_curr.len();
         let mut kl_divs = vec![vec![0.0; n]; n];
-        self.ts_solver.push_many(g);
 
-        let (ms_curr, vs_curr) = self.ts_solver.solve(true);
-        let prob = self.compute_prob_cmps();
+        for (i, j) in candidate_pairs(ms_curr, window) {
+            kl_divs[i][j] = self.expected_information_gain(i, j, prob, ms_curr, vs_curr);
+        }
 
-        for i in 1..n {
-            for j in 0..i {
-                let kl1 = {
-                    let (ms, vs) = self.ts_solver.solve_one((i, j));
-                    kl_divergence(&ms, &vs, &ms_curr, &vs_curr)
-                };
+        self.get_maximum(&kl_divs)
+    }
 
-                let kl2 = {
-                    let (ms, vs) = self.ts_solver.solve_one((j, i));
-                    kl_divergence(&ms, &vs, &ms_curr, &vs_curr)
-                };
+    // Expected KL divergence between the posterior after comparing i and j
+    // and the current one, over both outcomes. Two solver passes.
+    fn expected_information_gain(
+        &mut self,
+        i: usize,
+        j: usize,
+        prob: &[Vec<f64>],
+        ms_curr: &[f64],
+        vs_curr: &[f64],
+    ) -> f64 {
+        let kl1 = {
+            let (ms, vs) = self.ts_solver.solve_one((i, j));
+            kl_divergence(&ms, &vs, ms_curr, vs_curr)
+        };
+
+        let kl2 = {
+            let (ms, vs) = self.ts_solver.solve_one((j, i));
+            kl_divergence(&ms, &vs, ms_curr, vs_curr)
+        };
+
+        prob[i][j] * kl1 + prob[j][i] * kl2
+    }
 
-                kl_divs[i][j] = prob[i][j] * kl1 + prob[j][i] * kl2;
-            }
+    /// The `top_k` pairs with the highest expected information gain under the
+    /// last solve, best first, including pairs that were already compared so
+    /// well covered and neglected pairs can be told apart. Candidates are the
+    /// same as for `optimal_pair`; each costs two solver passes.
+    pub fn missing_comparisons_heatmap(&mut self, top_k: usize) -> Vec<PairGain> {
+        let (ms_curr, vs_curr) = (self.ts_solver.ms.clone(), self.ts_solver.vs.clone());
+        let prob = self.compute_prob_cmps();
+
+        let mut direct: HashMap<(usize, usize), usize> = HashMap::new();
+        for &[a, b] in &self.ts_solver.g {
+            *direct.entry((a.max(b), a.min(b))).or_default() += 1;
         }
 
-        let pair_to_compare = self.get_maximum(&kl_divs);
-        (pair_to_compare, prob, ms_curr, vs_curr)
+        let window = (ms_curr.len() > FULL_EIG_SEARCH_MAX_ITEMS).then_some(EIG_SEARCH_WINDOW);
+        let mut pairs: Vec<PairGain> = candidate_pairs(&ms_curr, window)
+            .into_iter()
+            .map(|(i, j)| {
+                // better-rated item first
+                let (a, b) = if ms_curr[i] >= ms_curr[j] { (i, j) } else { (j, i) };
+                PairGain {
+                    a,
+                    b,
+                    direct_comparisons: direct.get(&(i, j)).copied().unwrap_or(0),
+                    expected_information_gain: self
+                        .expected_information_gain(i, j, &prob, &ms_curr, &vs_curr),
+                    win_probability: prob[a][b],
+                }
+            })
+            .collect();
+        pairs.sort_by(|x, y| {
+            y.expected_information_gain
+                .partial_cmp(&x.expected_information_gain)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        pairs.truncate(top_k);
+        pairs
+    }
+
+    /// How likely `i` is to beat `j` after the last solve, and how much of
+    /// that is known. The band is the win probability with the skill
+    /// difference at either end of its ~95% interval: a 51% with a narrow
+    /// band is a genuinely close pair, with a wide one just a lack of data.
+    pub fn pair_probability(&self, i: usize, j: usize) -> PairProbability {
+        let (ms, vs) = (&self.ts_solver.ms, &self.ts_solver.vs);
+        let beta_sq = self.ts_solver.config.beta_sq;
+        let diff = ms[i] - ms[j];
+        let spread = PROBABILITY_BAND_Z * (vs[i] + vs[j]).sqrt();
+        PairProbability {
+            win_probability: ndtr(diff / (beta_sq + vs[i] + vs[j]).sqrt()),
+            variance: beta_sq + vs[i] + vs[j],
+            low: ndtr((diff - spread) / beta_sq.sqrt()),
+            high: ndtr((diff + spread) / beta_sq.sqrt()),
+        }
     }
 
     fn get_maximum(&self, gain_mat: &[Vec<f64>]) -> (usize, usize) {
@@ -101,7 +492,6 @@ impl ASAP {
         // let mut rng = thread_rng();
         let mut indices = Vec::new();
         let mut weights = Vec::new();
-        println!("gain_mat: {:?}", gain_mat);
 
         for (i, row) in gain_mat.iter().enumerate() {
             for (j, &gain) in row.iter().enumerate() {
@@ -119,21 +509,136 @@ impl ASAP {
             .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
             .unwrap()
             .0;
-        let chosen_pair = indices[chosen_index];
+        indices[chosen_index]
+    }
+}
 
-        let max_gain = gain_mat
-            .iter()
-            .flat_map(|row| row.iter().cloned())
-            .fold(0.0, f64::max);
+/// One entry of `ASAP::missing_comparisons_heatmap`.
+#[derive(Clone, Copy, Debug)]
+pub struct PairGain {
+    pub a: usize,
+    pub b: usize,
+    /// How often a and b were already compared directly
+    pub direct_comparisons: usize,
+    pub expected_information_gain: f64,
+    /// Model's probability that a beats b
+    pub win_probability: f64,
+}
 
-        println!(
-            "Chosen EIG: {}, Max EIG: {}",
-            gain_mat[chosen_pair.0][chosen_pair.1], max_gain
-        );
-        chosen_pair
+/// Result of `ASAP::pair_probability`.
+#[derive(Clone, Copy, Debug)]
+pub struct PairProbability {
+    pub win_probability: f64,
+    /// beta^2 + v_i + v_j, the variance of the performance difference
+    pub variance: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+// Rank of each item (0 = highest mean)
+fn ranks(ms: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..ms.len()).collect();
+    order.sort_by(|&a, &b| ms[b].partial_cmp(&ms[a]).unwrap_or(std::cmp::Ordering::Equal));
+    let mut rank = vec![0; ms.len()];
+    for (r, i) in order.into_iter().enumerate() {
+        rank[i] = r;
+    }
+    rank
+}
+
+// Pairs (i, j) with i > j to consider for the next comparison: all of them,
+// or with a window only each item and the `window` items just above it when
+// sorted by mean, O(n * window)
+fn candidate_pairs(ms: &[f64], window: Option<usize>) -> Vec<(usize, usize)> {
+    let n = ms.len();
+    match window {
+        None => (1..n).flat_map(|i| (0..i).map(move |j| (i, j))).collect(),
+        Some(w) => {
+            let mut by_mean: Vec<usize> = (0..n).collect();
+            by_mean.sort_by(|&a, &b| ms[a].partial_cmp(&ms[b]).unwrap_or(std::cmp::Ordering::Equal));
+            by_mean
+                .iter()
+                .enumerate()
+                .flat_map(|(pos, &i)| {
+                    by_mean[pos + 1..(pos + 1 + w).min(n)]
+                        .iter()
+                        .map(move |&j| (i.max(j), i.min(j)))
+                })
+                .collect()
+        }
     }
 }
 
+/// How the next pair to compare is chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum PairStrategy {
+    /// Maximise expected information gain (the ASAP default)
+    #[default]
+    Eig,
+    /// Binary insertion sort: place items one at a time, in creation order
+    Insertion,
+}
+
+impl std::str::FromStr for PairStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eig" => Ok(PairStrategy::Eig),
+            "insertion" => Ok(PairStrategy::Insertion),
+            _ => Err(format!("unknown strategy: {} (expected eig or insertion)", s)),
+        }
+    }
+}
+
+/// Every `[winner, loser]` comparison implied by a full ordering, best
+/// first: each item beats every item after it, n(n-1)/2 comparisons in all.
+pub fn group_comparisons(ordered: &[usize]) -> Vec<[usize; 2]> {
+    let mut cmps = Vec::with_capacity(ordered.len() * ordered.len().saturating_sub(1) / 2);
+    for (k, &winner) in ordered.iter().enumerate() {
+        for &loser in &ordered[k + 1..] {
+            cmps.push([winner, loser]);
+        }
+    }
+    cmps
+}
+
+/// Next comparison for a binary insertion sort.
+///
+/// Items are inserted in `creation_order`; the first item whose slot among the
+/// earlier items (sorted by `ms`, best first) isn't pinned down yet by its
+/// direct comparisons in `m` is compared against the middle of its remaining
+/// range. Returns `None` once every item has been placed.
+pub fn insertion_pair(
+    creation_order: &[usize],
+    ms: &[f64],
+    m: &[Vec<i32>],
+) -> Option<(usize, usize)> {
+    for k in 1..creation_order.len() {
+        let item = creation_order[k];
+        let mut sorted = creation_order[..k].to_vec();
+        sorted.sort_by(|&a, &b| ms[b].partial_cmp(&ms[a]).unwrap_or(std::cmp::Ordering::Equal));
+
+        // item goes into slot s, i.e. just above sorted[s]
+        let (mut lo, mut hi) = (0, sorted.len());
+        for (pos, &other) in sorted.iter().enumerate() {
+            if m[other][item] > 0 {
+                lo = lo.max(pos + 1);
+            }
+            if m[item][other] > 0 {
+                hi = hi.min(pos);
+            }
+        }
+
+        if lo < hi {
+            return Some((item, sorted[(lo + hi) / 2]));
+        }
+    }
+    None
+}
+
 struct FastUsizeSet {
     set: Vec<bool>,
     vec: Vec<usize>,
@@ -162,8 +667,14 @@ impl FastUsizeSet {
     }
 }
 
+// Weight of the new message when updating a comparison's messages
+const DAMPING: f64 = 0.5;
+// Prior precision of an anchor, high enough that comparisons can't move it
+const ANCHOR_PRECISION: f64 = 1e9;
+
 struct TrueSkillSolver {
     n: usize,
+    config: TrueSkillConfig,
     ms: Vec<f64>,
     vs: Vec<f64>,
     mgs: Vec<[f64; 2]>,
@@ -171,12 +682,21 @@ struct TrueSkillSolver {
     var_to_cmps0: Vec<Vec<usize>>,
     var_to_cmps1: Vec<Vec<usize>>,
     g: Vec<[usize; 2]>,
+    // draw margin per comparison (indexed like g), only allocated once one is set
+    draw_margins: Option<Vec<f64>>,
+    // outer iterations used by the last saved solve
+    iterations: usize,
+    // pinned score per anchor variable
+    anchors: HashMap<usize, f64>,
+    // prior mean per variable, where it isn't 0
+    prior_means: HashMap<usize, f64>,
 }
 
 impl TrueSkillSolver {
-    pub fn new(n: usize) -> Self {
+    pub fn new(n: usize, config: TrueSkillConfig) -> Self {
         TrueSkillSolver {
             n,
+            config,
             ms: vec![0.0; n],
             vs: vec![0.5; n],
             mgs: Vec::new(),
@@ -184,9 +704,33 @@ impl TrueSkillSolver {
             var_to_cmps0: vec![vec![]; n],
             var_to_cmps1: vec![vec![]; n],
             g: Vec::new(),
+            draw_margins: None,
+            iterations: 0,
+            anchors: HashMap::new(),
+            prior_means: HashMap::new(),
         }
     }
 
+    fn set_anchor(&mut self, p: usize, score: f64) {
+        self.anchors.insert(p, score);
+        self.ms[p] = score;
+        self.vs[p] = 1.0 / ANCHOR_PRECISION;
+    }
+
+    fn set_prior_mean(&mut self, p: usize, mean: f64) {
+        self.prior_means.insert(p, mean);
+        self.ms[p] = mean;
+    }
+
+    pub fn set_draw_margin(&mut self, comparison_id: usize, margin: f64) {
+        let n_cmps = self.g.len();
+        self.draw_margins.get_or_insert_with(|| vec![0.0; n_cmps])[comparison_id] = margin;
+    }
+
+    fn num_cmps(&self, p: usize) -> usize {
+        self.var_to_cmps0[p].len() + self.var_to_cmps1[p].len()
+    }
+
     fn push_many(&mut self, g: &[[usize; 2]]) {
         for &[a, b] in g {
             self.push_cmp(a, b);
@@ -197,6 +741,9 @@ impl TrueSkillSolver {
         self.pgs.push([0.0; 2]);
         self.mgs.push([0.0; 2]);
         self.g.push([i, j]);
+        if let Some(draw_margins) = &mut self.draw_margins {
+            draw_margins.push(0.0);
+        }
         let id = self.pgs.len() - 1;
         self.var_to_cmps0[i].push(id);
         self.var_to_cmps1[j].push(id);
@@ -206,6 +753,9 @@ impl TrueSkillSolver {
         self.pgs.pop();
         self.mgs.pop();
         self.g.pop();
+        if let Some(draw_margins) = &mut self.draw_margins {
+            draw_margins.pop();
+        }
         let id = self.pgs.len();
         self.var_to_cmps0[i].retain(|&x| x != id);
         self.var_to_cmps1[j].retain(|&x| x != id);
@@ -234,6 +784,20 @@ impl TrueSkillSolver {
         self._solve(todo_vars, todo_cmps, save, 0.001)
     }
 
+    // Saved solve that starts from the last pushed comparison only, assuming
+    // everything before it is already solved
+    fn solve_last(&mut self) -> (Vec<f64>, Vec<f64>) {
+        let Some(&[a, b]) = self.g.last() else {
+            return (self.ms.clone(), self.vs.clone());
+        };
+        let mut todo_vars = FastUsizeSet::new(self.n);
+        let mut todo_cmps = FastUsizeSet::new(self.g.len());
+        todo_vars.add(a);
+        todo_vars.add(b);
+        todo_cmps.add(self.g.len() - 1);
+        self._solve(todo_vars, todo_cmps, true, 0.001)
+    }
+
     pub fn solve_one(&mut self, cmp: (usize, usize)) -> (Vec<f64>, Vec<f64>) {
         self.push_cmp(cmp.0, cmp.1);
 
@@ -278,12 +842,14 @@ impl TrueSkillSolver {
 
         let g = &self.g;
 
+        let mut iterations = 0;
         for _i in 0..1000 {
             // println!("iter {}, todo_cmps {}", _i, todo_cmps.vec.len());
 
             if todo_cmps.vec.len() == 0 {
                 break;
             }
+            iterations += 1;
 
             while let Some(j) = todo_cmps.pop() {
                 // TODO to avoid bounds check but can't use nightly bc raj :(
@@ -302,21 +868,36 @@ impl TrueSkillSolver {
                 let msg0 = (ps[g[j][0]] * ms[g[j][0]] - pgs[j][0] * mgs[j][0]) / psg0;
                 let msg1 = (ps[g[j][1]] * ms[g[j][1]] - pgs[j][1] * mgs[j][1]) / psg1;
 
-                let vgt = 1.0 + 1.0 / psg0 + 1.0 / psg1;
+                // rarely compared items get a wider performance variance
+                let min_cmps = self.num_cmps(g[j][0]).min(self.num_cmps(g[j][1])).max(1);
+                let beta_sq = self.config.beta_sq + self.config.rare_item_beta_sq / min_cmps as f64;
+
+                let vgt = beta_sq + 1.0 / psg0 + 1.0 / psg1;
                 let mgt = msg0 - msg1;
 
-                let (ps_val, lmb) = psi_lamb(mgt / vgt.sqrt());
+                // the winner's performance has to beat the loser's by the margin
+                let margin = self.draw_margins.as_ref().map_or(0.0, |d| d[j]);
+                let (ps_val, lmb) = psi_lamb((mgt - margin) / vgt.sqrt());
                 let mt = mgt + vgt.sqrt() * ps_val;
                 let pt = 1.0 / (vgt * (1.0 - lmb));
 
                 let ptg = pt - 1.0 / vgt;
                 let mtg = (mt * pt - mgt / vgt) / (ptg + f64::EPSILON);
 
-                pgs[j][0] = 1.0 / (1.0 + 1.0 / ptg + 1.0 / psg1);
-                pgs[j][1] = 1.0 / (1.0 + 1.0 / ptg + 1.0 / psg0);
-
-                mgs[j][0] = msg1 + mtg;
-                mgs[j][1] = msg0 - mtg;
+                let new_pgs = [
+                    1.0 / (beta_sq + 1.0 / ptg + 1.0 / psg1),
+                    1.0 / (beta_sq + 1.0 / ptg + 1.0 / psg0),
+                ];
+                let new_mgs = [msg1 + mtg, msg0 - mtg];
+
+                // damp the update (in natural parameters): all comparisons in
+                // a sweep see the same marginals, and undamped, repeated
+                // comparisons between the same pair overshoot and oscillate
+                for k in 0..2 {
+                    let pm = DAMPING * new_pgs[k] * new_mgs[k] + (1.0 - DAMPING) * pgs[j][k] * mgs[j][k];
+                    pgs[j][k] = DAMPING * new_pgs[k] + (1.0 - DAMPING) * pgs[j][k];
+                    mgs[j][k] = if pgs[j][k] != 0.0 { pm / pgs[j][k] } else { 0.0 };
+                }
 
                 todo_vars.add(g[j][0]);
                 todo_vars.add(g[j][1]);
@@ -335,8 +916,17 @@ impl TrueSkillSolver {
                     sum_pgs_mgs[p] += pgs[i][1] * mgs[i][1];
                 }
 
-                let ps_ = 0.02 + sum_pgs[p];
-                let ms_ = sum_pgs_mgs[p] / ps_;
+                // anchors get a prior at their pinned score that no amount
+                // of evidence can outweigh
+                let (prior_p, prior_m) = match self.anchors.get(&p) {
+                    Some(&score) => (ANCHOR_PRECISION, score),
+                    None => (
+                        self.config.prior_precision,
+                        self.prior_means.get(&p).copied().unwrap_or(0.0),
+                    ),
+                };
+                let ps_ = prior_p + sum_pgs[p];
+                let ms_ = (prior_p * prior_m + sum_pgs_mgs[p]) / ps_;
 
                 if (ms_ - ms[p]).abs() > threshold || (ps_ - ps[p]).abs() > threshold {
                     // println!("p {} ms {} -> {} ps {} -> {}", p, ms[p], ms_, ps[p], ps_);
@@ -358,6 +948,7 @@ impl TrueSkillSolver {
         }
 
         if save {
+            self.iterations = iterations;
             self.vs = ps.iter().map(|&p| 1.0 / p).collect();
             self.ms = ms.clone();
             self.pgs = pgs;
@@ -445,9 +1036,380 @@ fn ndtr(a: f64) -> f64 {
     }
 }
 
+// Inverse of `ndtr` for p in (0, 1): Acklam's rational approximation,
+// polished with one Halley step to near double precision.
+fn ndtri(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    let x = if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    };
+
+    let e = ndtr(x) - p;
+    let u = e * (2.0 * PI).sqrt() * exp(x * x / 2.0);
+    x - u / (1.0 + x * u / 2.0)
+}
+
+// Below this, pdf / cdf is taken from the asymptotic series for the normal
+// tail instead: both underflow to 0 a little past -37, and an upset against a
+// much stronger prior lands out here.
+const PSI_LAMB_TAIL: f64 = -30.0;
+
 fn psi_lamb(x: f64) -> (f64, f64) {
+    if x < PSI_LAMB_TAIL {
+        // ndtr(x) ~ pdf(x) / -x * s with s = 1 - t r, t = 1 / x^2 and
+        // r = 1 - 3t + 15t^2 - ... (odd double factorials), so psi = -x / s
+        // and psi * (psi + x) = r / s^2
+        let t = 1.0 / (x * x);
+        let r = [1.0, 3.0, 15.0, 105.0, 945.0, 10395.0, 135135.0]
+            .iter()
+            .rev()
+            .fold(0.0, |acc, c| c - t * acc);
+        let s = 1.0 - t * r;
+        return (-x / s, r / (s * s));
+    }
     let p = exp(-x * x / 2.0) / (2.0 * PI).sqrt();
     let c = ndtr(x);
     let ps = p / c;
     (ps, ps * (ps + x))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Every case is generated from a fixed seed, so a failure names the seed
+    // that reproduces it
+    const CASES: u64 = 200;
+
+    fn solver(n: usize, cmps: &[[usize; 2]]) -> TrueSkillSolver {
+        let mut solver = TrueSkillSolver::new(n, TrueSkillConfig::default());
+        solver.push_many(cmps);
+        solver
+    }
+
+    // Random comparisons between distinct items, cycles and repeats included
+    fn random_cmps(rng: &mut StdRng, n: usize, count: usize) -> Vec<[usize; 2]> {
+        (0..count)
+            .map(|_| {
+                let a = rng.gen_range(0..n);
+                let b = (a + rng.gen_range(1..n)) % n;
+                [a, b]
+            })
+            .collect()
+    }
+
+    // 3 wins and 1 loss for the same pair: every comparison in a sweep reads
+    // the same marginals, so undamped updates overshoot, oscillate until the
+    // iteration cap and can end with the loser on top
+    #[test]
+    fn repeated_pair_converges() {
+        let mut solver = solver(2, &[[0, 1], [0, 1], [0, 1], [1, 0]]);
+        let (ms, _) = solver.solve(true);
+        assert!(ms[0] > ms[1], "ms {:?}", ms);
+        assert!(solver.iterations < 100, "{} iterations", solver.iterations);
+    }
+
+    #[test]
+    fn solve_stays_finite() {
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(2..40);
+            let count = rng.gen_range(0..300);
+            let mut solver = solver(n, &random_cmps(&mut rng, n, count));
+
+            let (ms, vs) = solver.solve(true);
+            assert!(ms.iter().all(|m| m.is_finite()), "seed {}: ms {:?}", seed, ms);
+            assert!(vs.iter().all(|&v| v.is_finite() && v > 0.0), "seed {}: vs {:?}", seed, vs);
+
+            let (a, b) = (rng.gen_range(0..n), rng.gen_range(0..n));
+            let (ms, vs) = solver.solve_one((a, b));
+            assert!(ms.iter().chain(&vs).all(|x| x.is_finite()), "seed {}: solve_one({}, {})", seed, a, b);
+        }
+    }
+
+    #[test]
+    fn lopsided_winner_outranks_loser() {
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(4..30);
+            let count = rng.gen_range(0..100);
+
+            // items 0 and 1 only meet each other, 0 winning nearly every time
+            let mut cmps: Vec<[usize; 2]> = random_cmps(&mut rng, n - 2, count)
+                .into_iter()
+                .map(|[a, b]| [a + 2, b + 2])
+                .collect();
+            let wins = rng.gen_range(3..10);
+            cmps.extend(std::iter::repeat_n([0, 1], wins));
+            cmps.push([1, 0]);
+
+            let (ms, _) = solver(n, &cmps).solve(true);
+            assert!(ms[0] > ms[1], "seed {}: {} vs {}", seed, ms[0], ms[1]);
+        }
+    }
+
+    #[test]
+    fn redundant_comparison_keeps_order() {
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(2..30);
+            let count = rng.gen_range(1..4 * n);
+            let mut cmps = random_cmps(&mut rng, n, count);
+            let (before, _) = solver(n, &cmps).solve(true);
+
+            // repeating a comparison we already have can only widen the
+            // winner's lead (up to the solver's convergence threshold)
+            let [winner, loser] = cmps[rng.gen_range(0..cmps.len())];
+            cmps.push([winner, loser]);
+            let (after, _) = solver(n, &cmps).solve(true);
+            assert!(
+                after[winner] - after[loser] > before[winner] - before[loser] - 0.01,
+                "seed {}: lead of {} over {} went from {} to {}",
+                seed,
+                winner,
+                loser,
+                before[winner] - before[loser],
+                after[winner] - after[loser]
+            );
+        }
+    }
+
+    #[test]
+    fn solve_is_order_independent() {
+        use rand::seq::SliceRandom;
+
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(2..30);
+            let count = rng.gen_range(0..4 * n);
+            let mut cmps = random_cmps(&mut rng, n, count);
+            let (ms, vs) = solver(n, &cmps).solve(true);
+
+            cmps.shuffle(&mut rng);
+            let (ms_shuffled, vs_shuffled) = solver(n, &cmps).solve(true);
+            for i in 0..n {
+                assert!(
+                    (ms[i] - ms_shuffled[i]).abs() < 0.01 && (vs[i] - vs_shuffled[i]).abs() < 0.01 * vs[i],
+                    "seed {}: item {} went from ({}, {}) to ({}, {}) after shuffling",
+                    seed,
+                    i,
+                    ms[i],
+                    vs[i],
+                    ms_shuffled[i],
+                    vs_shuffled[i]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ndtri_inverts_ndtr() {
+        for i in 1..1000 {
+            let p = i as f64 / 1000.0;
+            let x = ndtri(p);
+            assert!((ndtr(x) - p).abs() < 1e-12, "p {}: ndtr(ndtri(p)) = {}", p, ndtr(x));
+        }
+        assert_eq!(ndtri(0.5), 0.0);
+    }
+
+    #[test]
+    fn incremental_solve_matches_full_solve() {
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(2..40);
+            let count = rng.gen_range(1..300);
+            let cmps = random_cmps(&mut rng, n, count);
+            let (last, earlier) = cmps.split_last().unwrap();
+
+            // against the full re-solve it replaces, which also starts from
+            // the messages of the earlier solve
+            let mut full = ASAP::new(n);
+            full.add_comparison_batch(earlier);
+            let (ms_full, _) = full.add_comparison_batch(&[*last]);
+
+            let config = TrueSkillConfig { incremental_updates: true, ..TrueSkillConfig::default() };
+            let mut asap = ASAP::with_config(n, config);
+            asap.add_comparison_batch(earlier);
+            let (ms, _) = asap.add_comparison_batch(&[*last]);
+            for i in 0..n {
+                assert!(
+                    (ms[i] - ms_full[i]).abs() < 0.05,
+                    "seed {}: item {} is {} incrementally but {} fully solved",
+                    seed,
+                    i,
+                    ms[i],
+                    ms_full[i]
+                );
+            }
+        }
+    }
+
+    // Random posterior: means in [-10, 10], variances in [0.01, 10]
+    fn random_posterior(rng: &mut StdRng, n: usize) -> (Vec<f64>, Vec<f64>) {
+        let ms = (0..n).map(|_| rng.gen_range(-10.0..10.0)).collect();
+        let vs = (0..n).map(|_| rng.gen_range(0.01..10.0)).collect();
+        (ms, vs)
+    }
+
+    #[test]
+    fn kl_divergence_of_same_posterior_is_zero() {
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(1..40);
+            let (ms, vs) = random_posterior(&mut rng, n);
+            let kl = kl_divergence(&ms, &vs, &ms, &vs);
+            assert!(kl.abs() < 1e-9, "seed {}: kl {}", seed, kl);
+        }
+    }
+
+    // Posterior pairs over the same 1..40 items: means in [-10, 10],
+    // variances in [0.01, 10]
+    fn posterior_pairs() -> impl Strategy<Value = (Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>)> {
+        (1..40usize).prop_flat_map(|n| {
+            let ms = || prop::collection::vec(-10.0..10.0, n);
+            let vs = || prop::collection::vec(0.01..10.0, n);
+            (ms(), vs(), ms(), vs())
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn kl_divergence_is_non_negative((ms1, vs1, ms2, vs2) in posterior_pairs()) {
+            let kl = kl_divergence(&ms1, &vs1, &ms2, &vs2);
+            prop_assert!(kl.is_finite() && kl >= 0.0, "kl {}", kl);
+        }
+    }
+
+    #[test]
+    fn kl_div_pairs_grows_with_distance() {
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(2..40);
+            let (ms, vs) = random_posterior(&mut rng, n);
+            let shift: Vec<f64> = (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            let moved = |scale: f64| -> Vec<f64> { ms.iter().zip(&shift).map(|(m, d)| m + scale * d).collect() };
+
+            let near = kl_div_pairs(&ms, &vs, &moved(0.1), &vs);
+            let far = kl_div_pairs(&ms, &vs, &moved(1.0), &vs);
+            assert_eq!(kl_div_pairs(&ms, &vs, &ms, &vs), 0.0);
+            assert!(near < far, "seed {}: {} for the near posterior, {} for the far one", seed, near, far);
+        }
+    }
+
+    #[test]
+    fn ndtr_limits() {
+        assert!((ndtr(0.0) - 0.5).abs() < 1e-15);
+        assert_eq!(ndtr(f64::NEG_INFINITY), 0.0);
+        assert_eq!(ndtr(f64::INFINITY), 1.0);
+        assert!(ndtr(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn psi_lamb_is_finite() {
+        // far enough out that pdf and cdf both underflow
+        for i in -100_000..=1000 {
+            let x = i as f64 / 100.0;
+            let (psi, lamb) = psi_lamb(x);
+            assert!(psi.is_finite() && lamb.is_finite(), "x {}: ({}, {})", x, psi, lamb);
+            // pt = 1 / (v (1 - lamb)) has to stay positive and finite
+            assert!(psi >= 0.0 && (0.0..1.0).contains(&lamb), "x {}: ({}, {})", x, psi, lamb);
+        }
+        // the tail series picks up where the direct ratio leaves off
+        let (direct, tail) = (psi_lamb(PSI_LAMB_TAIL), psi_lamb(PSI_LAMB_TAIL - 1e-12));
+        assert!((direct.0 - tail.0).abs() < 1e-9 && (direct.1 - tail.1).abs() < 1e-9, "{:?} {:?}", direct, tail);
+    }
+
+    #[test]
+    fn upset_against_strong_prior_does_not_panic() {
+        // item 1 beats item 0 with priors far enough apart that the solver's
+        // first message lands deep in the normal tail
+        let mut asap = ASAP::new(2);
+        asap.set_prior(0, 100.0);
+        asap.set_prior(1, -100.0);
+        let (ms, vs) = asap.add_comparison_batch(&[[1, 0]]);
+        assert!(ms.iter().chain(&vs).all(|x| x.is_finite()), "ms {:?} vs {:?}", ms, vs);
+        assert!(ms[1] > -100.0 && ms[0] < 100.0, "ms {:?}", ms);
+    }
+
+    #[test]
+    fn draw_margins_apply_per_comparison() {
+        // two identical wins of 0 over 1, only the second with a margin
+        let mut with_margin = ASAP::new(2);
+        with_margin.set_draw_margin(1, 2.0);
+        let (ms, _) = with_margin.add_comparison_batch(&[[0, 1], [0, 1]]);
+        let (plain, _) = ASAP::new(2).add_comparison_batch(&[[0, 1], [0, 1]]);
+        let (both, _) = {
+            let mut asap = ASAP::new(2);
+            asap.set_draw_margin(0, 2.0);
+            asap.set_draw_margin(1, 2.0);
+            asap.add_comparison_batch(&[[0, 1], [0, 1]])
+        };
+        let gap = |ms: &[f64]| ms[0] - ms[1];
+        assert!(gap(&plain) < gap(&ms) && gap(&ms) < gap(&both), "{:?} {:?} {:?}", plain, ms, both);
+
+        // a huge margin pushes the solver deep into the normal tail
+        let mut asap = ASAP::new(2);
+        asap.set_draw_margin(0, 500.0);
+        let (ms, vs) = asap.add_comparison_batch(&[[0, 1]]);
+        assert!(ms.iter().chain(&vs).all(|x| x.is_finite()), "ms {:?} vs {:?}", ms, vs);
+    }
+
+    #[test]
+    fn extreme_anchor_losing_does_not_panic() {
+        let mut asap = ASAP::new(2);
+        asap.pin_anchor(0, 100.0);
+        let (ms, vs) = asap.add_comparison_batch(&[[1, 0]]);
+        assert!(ms.iter().chain(&vs).all(|x| x.is_finite()), "ms {:?} vs {:?}", ms, vs);
+        // the anchor holds and the item that beat it is pulled up towards it
+        assert!((ms[0] - 100.0).abs() < 1e-3 && ms[1] > 90.0, "ms {:?}", ms);
+    }
+}
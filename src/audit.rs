@@ -0,0 +1,69 @@
+// Optional append-only audit of how the model responded to each vote: one
+// JSON line per comparison with the scores it moved, written to the path in
+// AUDIT_LOG. ratings.log (or the stored comparisons) only hold the votes.
+
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+
+// Items other than the compared pair that moved less than this aren't listed
+const MIN_DELTA: f64 = 0.01;
+
+/// The audit log path, if AUDIT_LOG is set.
+pub fn path_from_env() -> Option<String> {
+    std::env::var("AUDIT_LOG").ok().filter(|s| !s.is_empty())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreChange {
+    id: usize,
+    content: String,
+    before: f64,
+    after: f64,
+    delta: f64,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    timestamp: String,
+    pair: [usize; 2],
+    winner: usize,
+    changes: &'a [ScoreChange],
+}
+
+/// Score changes between two solves. `items[i]` is the (id, content) of
+/// solver index i, or None for indices that stand for no item. The compared
+/// `pair` (solver indices) is always listed first, then every other item that
+/// moved by at least MIN_DELTA, largest move first.
+pub fn score_changes(
+    items: &[Option<(usize, &str)>],
+    before: &[f64],
+    after: &[f64],
+    pair: [usize; 2],
+) -> Vec<ScoreChange> {
+    let change = |i: usize| {
+        items[i].map(|(id, content)| ScoreChange {
+            id,
+            content: content.to_string(),
+            before: before[i],
+            after: after[i],
+            delta: after[i] - before[i],
+        })
+    };
+    let mut others: Vec<ScoreChange> = (0..items.len())
+        .filter(|i| !pair.contains(i))
+        .filter_map(change)
+        .filter(|c| c.delta.abs() >= MIN_DELTA)
+        .collect();
+    others.sort_by(|a, b| b.delta.abs().total_cmp(&a.delta.abs()));
+    pair.into_iter().filter_map(change).chain(others).collect()
+}
+
+/// Append one comparison (by item id) and the changes it caused to the log.
+pub fn append(path: &str, pair: [usize; 2], winner: usize, changes: &[ScoreChange]) -> io::Result<()> {
+    let entry = Entry { timestamp: chrono::Utc::now().to_rfc3339(), pair, winner, changes };
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    // one write per line, so concurrent writers don't interleave within it
+    File::options().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
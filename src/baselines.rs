@@ -0,0 +1,69 @@
+// Simple rating models to compare the TrueSkill solver against. Both take the
+// same win matrix as ASAP (m[i][j] = times i beat j) and return one score per
+// item, higher is better.
+
+const BT_MAX_ITERS: usize = 200;
+const BT_TOLERANCE: f64 = 1e-6;
+
+/// Bradley-Terry strengths fitted with the MM algorithm (Hunter 2004),
+/// returned as log-strengths so they are on a similar scale to TrueSkill means.
+///
+/// Every item gets one virtual win and one virtual loss against an opponent of
+/// strength 1, which keeps unbeaten / winless items finite.
+pub fn bradley_terry(m: &[Vec<i32>]) -> Vec<f64> {
+    let n = m.len();
+    let wins: Vec<f64> = (0..n)
+        .map(|i| (0..n).filter(|&j| j != i).map(|j| m[i][j] as f64).sum::<f64>() + 1.0)
+        .collect();
+
+    let mut p = vec![1.0; n];
+    for _ in 0..BT_MAX_ITERS {
+        let mut next: Vec<f64> = (0..n)
+            .map(|i| {
+                let mut denom = 2.0 / (p[i] + 1.0);
+                for j in 0..n {
+                    let games = m[i][j] + m[j][i];
+                    if j != i && games > 0 {
+                        denom += games as f64 / (p[i] + p[j]);
+                    }
+                }
+                wins[i] / denom
+            })
+            .collect();
+
+        // pin the geometric mean to 1, the strengths are only defined up to scale
+        let log_mean = next.iter().map(|x| x.ln()).sum::<f64>() / n as f64;
+        let scale = (-log_mean).exp();
+        next.iter_mut().for_each(|x| *x *= scale);
+
+        let delta = next
+            .iter()
+            .zip(&p)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        p = next;
+        if delta < BT_TOLERANCE {
+            break;
+        }
+    }
+    p.iter().map(|x| x.ln()).collect()
+}
+
+/// Fraction of direct comparisons won, 0.5 for items never compared.
+pub fn win_rate(m: &[Vec<i32>]) -> Vec<f64> {
+    let n = m.len();
+    (0..n)
+        .map(|i| {
+            let (mut won, mut played) = (0, 0);
+            for j in (0..n).filter(|&j| j != i) {
+                won += m[i][j];
+                played += m[i][j] + m[j][i];
+            }
+            if played == 0 {
+                0.5
+            } else {
+                won as f64 / played as f64
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,45 @@
+use crate::plot_ci::escape_xml;
+
+/// Render ranked `(content, score)` items, best first, as a self-contained
+/// HTML fragment (inline styles only) that can be dropped into any element.
+pub fn rankings_html(items: &[(String, f64)], dark: bool) -> String {
+    let (background, text, track, bar) = if dark {
+        ("#1f2937", "#f9fafb", "#374151", "#60a5fa")
+    } else {
+        ("#ffffff", "#111827", "#e5e7eb", "#2563eb")
+    };
+
+    let min_score = items.iter().map(|&(_, s)| s).fold(f64::INFINITY, f64::min);
+    let max_score = items.iter().map(|&(_, s)| s).fold(f64::NEG_INFINITY, f64::max);
+    let range = max_score - min_score;
+
+    let mut html = format!(
+        r#"<div class="todo-sorter-embed" style="font-family:Arial,sans-serif;font-size:14px;background:{};color:{};padding:12px;border-radius:6px">"#,
+        background, text
+    );
+    html.push_str(r#"<ol style="margin:0;padding-left:1.5em">"#);
+
+    for (content, score) in items {
+        // Bars run from 10% for the lowest shown score to 100% for the highest
+        let width = if range > 0.0 {
+            10.0 + 90.0 * (score - min_score) / range
+        } else {
+            100.0
+        };
+        html.push_str(&format!(
+            r#"<li style="margin:6px 0" title="score {:.2}"><div>{}</div><div style="background:{};height:6px;border-radius:3px;margin-top:2px"><div style="width:{:.1}%;background:{};height:100%;border-radius:3px"></div></div></li>"#,
+            score,
+            escape_xml(content),
+            track,
+            width,
+            bar
+        ));
+    }
+
+    html.push_str("</ol>");
+    html.push_str(
+        r#"<div style="font-size:11px;opacity:0.7;margin-top:8px">Powered by todo-sorter</div>"#,
+    );
+    html.push_str("</div>");
+    html
+}
@@ -1,11 +1,17 @@
 // #![feature(let_chains)]
 // #![feature(core_intrinsics)]
 mod asap_cpu;
+mod audit;
+mod baselines;
+mod embed;
 mod mp;
+mod pdf_export;
 mod plot_ci;
 pub mod plot_ratings;
+mod similarity;
 mod sorter;
 mod web_service;
+mod webhook;
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,108 @@
+use crate::plot_ci::truncate_text;
+use printpdf::{BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point};
+
+const PAGE_WIDTH: f32 = 210.0; // A4
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ROWS_PER_PAGE: usize = 40;
+const ROW_HEIGHT: f32 = 6.0;
+
+// Column x positions
+const RANK_X: f32 = MARGIN;
+const TASK_X: f32 = MARGIN + 15.0;
+const SCORE_X: f32 = PAGE_WIDTH - MARGIN - 45.0;
+const COMPARISONS_X: f32 = PAGE_WIDTH - MARGIN - 22.0;
+
+// The builtin PDF fonts only cover Latin-1, so replace anything else
+// (emojis, CJK, ...) rather than emitting garbage glyphs
+fn pdf_safe(s: &str) -> String {
+    s.chars()
+        .map(|c| if (c as u32) < 0x100 { c } else { '?' })
+        .collect()
+}
+
+fn draw_rule(layer: &PdfLayerReference, y: f32) {
+    layer.set_outline_thickness(0.5);
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(MARGIN), Mm(y)), false),
+            (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false),
+        ],
+        is_closed: false,
+    });
+}
+
+fn draw_table_header(layer: &PdfLayerReference, font: &IndirectFontRef, y: f32) {
+    layer.use_text("Rank", 10.0, Mm(RANK_X), Mm(y), font);
+    layer.use_text("Task", 10.0, Mm(TASK_X), Mm(y), font);
+    layer.use_text("Score", 10.0, Mm(SCORE_X), Mm(y), font);
+    layer.use_text("Comparisons", 10.0, Mm(COMPARISONS_X), Mm(y), font);
+    draw_rule(layer, y - 2.0);
+}
+
+/// Render a ranked task table as a PDF document held in memory.
+///
+/// `rows` are `(rank, task, score, comparisons)`; the table is split
+/// across pages of `ROWS_PER_PAGE` rows and `footer` is printed at the
+/// bottom of every page.
+pub fn rankings_pdf(
+    title: &str,
+    rows: &[(usize, String, f64, usize)],
+    footer: &str,
+) -> Result<Vec<u8>, printpdf::Error> {
+    let (doc, first_page, first_layer) =
+        PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let chunks: Vec<_> = if rows.is_empty() {
+        vec![rows]
+    } else {
+        rows.chunks(ROWS_PER_PAGE).collect()
+    };
+    let page_count = chunks.len();
+
+    for (page_num, chunk) in chunks.into_iter().enumerate() {
+        let layer = if page_num == 0 {
+            doc.get_page(first_page).get_layer(first_layer)
+        } else {
+            let (page, layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            doc.get_page(page).get_layer(layer)
+        };
+
+        let mut y = PAGE_HEIGHT - MARGIN;
+        if page_num == 0 {
+            layer.use_text(pdf_safe(title), 18.0, Mm(MARGIN), Mm(y), &bold);
+            y -= 10.0;
+        }
+
+        draw_table_header(&layer, &bold, y);
+        y -= ROW_HEIGHT + 1.0;
+
+        for (rank, task, score, comparisons) in chunk {
+            layer.use_text(rank.to_string(), 10.0, Mm(RANK_X), Mm(y), &font);
+            layer.use_text(
+                pdf_safe(&truncate_text(task, 70)),
+                10.0,
+                Mm(TASK_X),
+                Mm(y),
+                &font,
+            );
+            layer.use_text(format!("{:.3}", score), 10.0, Mm(SCORE_X), Mm(y), &font);
+            layer.use_text(comparisons.to_string(), 10.0, Mm(COMPARISONS_X), Mm(y), &font);
+            y -= ROW_HEIGHT;
+        }
+
+        draw_rule(&layer, MARGIN + 5.0);
+        layer.use_text(pdf_safe(footer), 8.0, Mm(MARGIN), Mm(MARGIN), &font);
+        layer.use_text(
+            format!("Page {} of {}", page_num + 1, page_count),
+            8.0,
+            Mm(PAGE_WIDTH - MARGIN - 20.0),
+            Mm(MARGIN),
+            &font,
+        );
+    }
+
+    doc.save_to_bytes()
+}
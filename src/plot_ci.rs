@@ -1,7 +1,7 @@
 use std::fs::File;
 use std::io::Write;
 
-fn escape_xml(s: &str) -> String {
+pub(crate) fn escape_xml(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -9,7 +9,30 @@ fn escape_xml(s: &str) -> String {
         .replace('\'', "&#39;")
 }
 
-pub fn plot_ci(mut items: Vec<(String, f64, f64)>, filename: &str) -> std::io::Result<()> {
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlotOptions {
+    // light text and lines on a dark background
+    pub dark_mode: bool,
+}
+
+// items are (label, mean, variance, number of comparisons); better supported
+// estimates get a bigger dot and a thicker interval line
+pub fn plot_ci(items: Vec<(String, f64, f64, usize)>, filename: &str) -> std::io::Result<()> {
+    plot_ci_with_options(items, &PlotOptions::default(), &mut File::create(filename)?)
+}
+
+// plot_ci, written as SVG to any writer
+pub fn plot_ci_with_options(
+    mut items: Vec<(String, f64, f64, usize)>,
+    options: &PlotOptions,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    let (background, foreground, separator, dot) = if options.dark_mode {
+        ("#1f2937", "#f9fafb", "#4b5563", "#60a5fa")
+    } else {
+        ("white", "black", "#ccc", "blue")
+    };
+
     // Sort items by mean in descending order
     items.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
     let graph_width = 400.0;
@@ -21,66 +44,72 @@ pub fn plot_ci(mut items: Vec<(String, f64, f64)>, filename: &str) -> std::io::R
 
     let min_mean = items
         .iter()
-        .map(|&(_, m, _)| m)
+        .map(|&(_, m, _, _)| m)
         .fold(f64::INFINITY, f64::min);
     let max_mean = items
         .iter()
-        .map(|&(_, m, _)| m)
+        .map(|&(_, m, _, _)| m)
         .fold(f64::NEG_INFINITY, f64::max);
-    let range = max_mean - min_mean;
+    // all means equal (e.g. nothing compared yet): avoid dividing by zero
+    let range = if max_mean > min_mean { max_mean - min_mean } else { 1.0 };
 
     let mut svg = format!(
         r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
         total_width, height
     );
 
-    // Add a white background
+    // Add the background
     svg.push_str(&format!(
-        r#"<rect width="100%" height="100%" fill="white"/>"#
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        background
     ));
 
     // Add vertical line separating labels from graph
     svg.push_str(&format!(
-        r#"<line x1="{}" y1="0" x2="{}" y2="{}" stroke="\#ccc" stroke-width="1"/>"#,
-        left_margin, left_margin, height
+        r#"<line x1="{}" y1="0" x2="{}" y2="{}" stroke="{}" stroke-width="1"/>"#,
+        left_margin, left_margin, height, separator
     ));
 
-    for (i, (name, mean, var)) in items.iter().enumerate() {
+    for (i, (name, mean, var, count)) in items.iter().enumerate() {
         let y = i as f64 * row_height + 20.0; // Add some top padding
         let x = left_margin + (graph_width * (max_mean - mean) / range);
         let ci_width = graph_width * (var.sqrt() * 1.645) / range; // 90% confidence interval
+        let radius = 2.0 + (*count as f64).sqrt().min(8.0);
+        let stroke_width = 1.0 + *count as f64 / 20.0;
 
         // Add confidence interval line
         svg.push_str(&format!(
-            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="black" stroke-width="2"/>"#,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{:.1}"/>"#,
             x - ci_width / 2.0,
             y,
             x + ci_width / 2.0,
-            y
+            y,
+            foreground,
+            stroke_width
         ));
 
         // Add mean point
         svg.push_str(&format!(
-            r#"<circle cx="{}" cy="{}" r="4" fill="blue"/>"#,
-            x, y
+            r#"<circle cx="{}" cy="{}" r="{:.1}" fill="{}"/>"#,
+            x, y, radius, dot
         ));
 
         // Add label to the left of the CI
         let label_x = x + ci_width / 2.0 + 10.0;
         svg.push_str(&format!(
-            r#"<text x="{}" y="{}" font-family="Arial, sans-serif" font-size="14" text-anchor="start" dominant-baseline="middle">{}</text>"#,
+            r#"<text x="{}" y="{}" font-family="Arial, sans-serif" font-size="14" fill="{}" text-anchor="start" dominant-baseline="middle">{}</text>"#,
             label_x,
             y,
+            foreground,
             escape_xml(&truncate_text(name, 200))
         ));
     }
 
     svg.push_str("</svg>");
-    File::create(filename)?.write_all(svg.as_bytes())?;
-    Ok(())
+    out.write_all(svg.as_bytes())
 }
 
-fn truncate_text(s: &str, max_chars: usize) -> String {
+pub(crate) fn truncate_text(s: &str, max_chars: usize) -> String {
     if s.chars().count() <= max_chars {
         s.to_string()
     } else {
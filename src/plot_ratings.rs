@@ -1,3 +1,7 @@
+use petgraph::algo::toposort;
+use petgraph::algo::tred::{dag_to_toposorted_adjacency_list, dag_transitive_reduction_closure};
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::IntoNeighbors;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufRead, Write};
@@ -42,47 +46,94 @@ fn dfs(
     }
 }
 
-fn transitive_reduction(edges: &mut HashMap<(usize, usize), f64>, ok: &impl Fn(&f64) -> bool) {
-    // let mut edges_to_remove = Vec::new();
+/// Remove every edge that satisfies `ok` and is implied by a longer path of
+/// `ok` edges, i.e. the transitive reduction of the `ok` subgraph. Edges that
+/// don't satisfy `ok` are left alone.
+pub fn transitive_reduction(edges: &mut HashMap<(usize, usize), f64>, ok: &impl Fn(&f64) -> bool) {
     let mut map: HashMap<usize, Vec<usize>> = HashMap::new();
     let mut nodes: Vec<usize> = edges.keys().map(|(x, _)| *x).collect();
     nodes.sort();
+    nodes.dedup();
 
-    for (from, to) in edges.keys() {
-        map.entry(*from).or_insert_with(Vec::new).push(*to);
+    for ((from, to), p) in edges.iter() {
+        if ok(p) {
+            map.entry(*from).or_default().push(*to);
+        }
     }
 
-    let mut edges_to_remove = HashSet::new();
+    // (from, to) edges found to be implied by a longer path
+    let mut edges_to_remove: HashSet<(usize, usize)> = HashSet::new();
+    // nodes reachable from a child of n1 other than directly
+    let mut reachable: HashSet<usize> = HashSet::new();
     let mut dfs_buf: HashSet<usize> = HashSet::new();
 
     for n1 in nodes.iter() {
         for n2 in map.get(n1).unwrap_or(&vec![]) {
-            if edges_to_remove.contains(n2) {
+            // everything below n2 is already covered
+            if reachable.contains(n2) {
                 continue;
             }
 
             dfs_buf.insert(*n1);
-            dfs(&map, *n2, &mut dfs_buf, &edges, ok);
+            dfs(&map, *n2, &mut dfs_buf, edges, ok);
             for n3 in dfs_buf.iter() {
                 if n2 == n3 || n1 == n3 {
                     continue;
                 }
-                edges_to_remove.insert(*n3);
+                reachable.insert(*n3);
+                if edges.get(&(*n1, *n3)).is_some_and(ok) {
+                    edges_to_remove.insert((*n1, *n3));
+                }
             }
             dfs_buf.clear();
-            // }
         }
 
-        for y in edges_to_remove.iter() {
-            edges.remove(&(*n1, *y));
-            map.get_mut(n1)
-                .unwrap()
-                .retain(|z| !edges_to_remove.contains(z));
+        if let Some(children) = map.get_mut(n1) {
+            children.retain(|z| !edges_to_remove.contains(&(*n1, *z)));
         }
-        edges_to_remove.clear();
+        reachable.clear();
+    }
+
+    for edge in &edges_to_remove {
+        edges.remove(edge);
     }
 }
 
+/// Same as `transitive_reduction`, using petgraph's DAG reduction. The `ok`
+/// subgraph has to be acyclic; otherwise nothing is removed.
+#[allow(dead_code)] // benchmarked against transitive_reduction
+pub fn transitive_reduction_petgraph(
+    edges: &mut HashMap<(usize, usize), f64>,
+    ok: &impl Fn(&f64) -> bool,
+) {
+    let mut graph: DiGraph<usize, ()> = DiGraph::new();
+    let mut node_of: HashMap<usize, NodeIndex> = HashMap::new();
+    let mut node = |graph: &mut DiGraph<usize, ()>, id: usize| {
+        *node_of.entry(id).or_insert_with(|| graph.add_node(id))
+    };
+    for ((from, to), p) in edges.iter() {
+        if ok(p) {
+            let (a, b) = (node(&mut graph, *from), node(&mut graph, *to));
+            graph.add_edge(a, b, ());
+        }
+    }
+
+    let Ok(order) = toposort(&graph, None) else {
+        return;
+    };
+    let (adjacency, _) = dag_to_toposorted_adjacency_list::<_, u32>(&graph, &order);
+    let (reduction, _) = dag_transitive_reduction_closure(&adjacency);
+
+    let mut kept: HashSet<(usize, usize)> = HashSet::new();
+    // the reduction's nodes are positions in the topological order
+    for from in reduction.node_indices() {
+        for to in reduction.neighbors(from) {
+            kept.insert((graph[order[from as usize]], graph[order[to as usize]]));
+        }
+    }
+    edges.retain(|edge, p| !ok(p) || kept.contains(edge));
+}
+
 pub fn plot_ratings(
     input_file: &str,
     output_file: &str,
@@ -215,3 +266,47 @@ pub fn plot_ratings(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn reduction_only_drops_implied_edges_of_its_class() {
+        // 1 > 2 > 3, with 1 > 3 implied; 3 -> 1 is in the other class
+        let mut edges = HashMap::from([((1, 2), 0.8), ((2, 3), 0.8), ((1, 3), 0.9), ((3, 1), 0.1)]);
+        transitive_reduction(&mut edges, &|p| *p > 0.5);
+        let mut kept: Vec<_> = edges.keys().copied().collect();
+        kept.sort();
+        assert_eq!(kept, vec![(1, 2), (2, 3), (3, 1)]);
+    }
+
+    #[test]
+    fn dfs_and_petgraph_reductions_agree() {
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let n = rng.gen_range(2..40);
+            let ratings: Vec<f64> = (0..n).map(|_| rng.gen_range(-3.0..3.0)).collect();
+            let mut edges = HashMap::new();
+            for _ in 0..n * 4 {
+                let a = rng.gen_range(0..n);
+                let b = (a + rng.gen_range(1..n)) % n;
+                edges.insert((a, b), 1.0 / (1.0 + f64::exp(ratings[b] - ratings[a])));
+            }
+
+            let mut dfs = edges.clone();
+            transitive_reduction(&mut dfs, &|p| *p > 0.5);
+            transitive_reduction(&mut dfs, &|p| *p < 0.5);
+            let mut petgraph = edges;
+            transitive_reduction_petgraph(&mut petgraph, &|p| *p > 0.5);
+            transitive_reduction_petgraph(&mut petgraph, &|p| *p < 0.5);
+
+            let mut dfs: Vec<_> = dfs.into_keys().collect();
+            let mut petgraph: Vec<_> = petgraph.into_keys().collect();
+            dfs.sort();
+            petgraph.sort();
+            assert_eq!(dfs, petgraph, "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Fuzzy matching of todo texts, for spotting the same task written twice
+// ("Call dentist" / "call the dentist"). Texts are compared after lowercasing,
+// dropping punctuation and collapsing whitespace.
+
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// How similar two todo texts are, from 0 (nothing in common) to 1 (the same
+/// after normalisation).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SimilarityMetric {
+    /// 1 - edit distance / length of the longer text
+    #[default]
+    Levenshtein,
+    /// Shared words / all words (Jaccard index of the word sets)
+    Tokens,
+}
+
+impl SimilarityMetric {
+    // Expects texts that went through `normalize`
+    fn similarity(self, a: &str, b: &str) -> f64 {
+        match self {
+            SimilarityMetric::Levenshtein => {
+                let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+                let longest = a.len().max(b.len());
+                if longest == 0 {
+                    return 1.0;
+                }
+                1.0 - levenshtein(&a, &b) as f64 / longest as f64
+            }
+            SimilarityMetric::Tokens => {
+                let a: HashSet<&str> = a.split(' ').filter(|w| !w.is_empty()).collect();
+                let b: HashSet<&str> = b.split(' ').filter(|w| !w.is_empty()).collect();
+                let union = a.union(&b).count();
+                if union == 0 {
+                    return 1.0;
+                }
+                a.intersection(&b).count() as f64 / union as f64
+            }
+        }
+    }
+
+    // Cheap upper bound on `similarity` from the lengths alone, to skip pairs
+    // that can't reach the threshold without computing the full metric
+    fn length_bound(self, a: &str, b: &str) -> f64 {
+        match self {
+            SimilarityMetric::Levenshtein => {
+                let (la, lb) = (a.chars().count(), b.chars().count());
+                if la.max(lb) == 0 {
+                    1.0
+                } else {
+                    la.min(lb) as f64 / la.max(lb) as f64
+                }
+            }
+            SimilarityMetric::Tokens => 1.0,
+        }
+    }
+}
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Group `(id, text)` items whose texts are at least `threshold` similar,
+/// transitively: if a~b and b~c then a, b and c end up in one cluster even if
+/// a and c are further apart. Only clusters of two or more are returned, each
+/// sorted by id, and the clusters are sorted by their smallest id.
+pub fn duplicate_clusters(
+    items: &[(usize, &str)],
+    metric: SimilarityMetric,
+    threshold: f64,
+) -> Vec<Vec<usize>> {
+    let normalized: Vec<String> = items.iter().map(|(_, text)| normalize(text)).collect();
+
+    // union-find over item positions
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for i in 0..items.len() {
+        for j in i + 1..items.len() {
+            if metric.length_bound(&normalized[i], &normalized[j]) < threshold {
+                continue;
+            }
+            if metric.similarity(&normalized[i], &normalized[j]) >= threshold {
+                let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                parent[ri] = rj;
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(id, _)) in items.iter().enumerate() {
+        let r = root(&mut parent, i);
+        clusters.entry(r).or_default().push(id);
+    }
+    let mut clusters: Vec<Vec<usize>> = clusters.into_values().filter(|c| c.len() > 1).collect();
+    for cluster in &mut clusters {
+        cluster.sort_unstable();
+    }
+    clusters.sort_unstable();
+    clusters
+}
@@ -1,163 +1,791 @@
 use rand::Rng;
+use serde::Serialize;
 use tuple_map::TupleMap2;
 
-use crate::asap_cpu::ASAP;
+use crate::asap_cpu::{insertion_pair, PairStrategy, ASAP};
+use crate::audit;
 use crate::plot_ci::plot_ci;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufRead, Write};
 use std::process::Command;
+use std::sync::{mpsc, Mutex, OnceLock};
 use std::thread;
+use std::time::Duration;
 
 const EMOJIS: &[&str] = &["📅", "⏳", "✅"];
+const DEFAULT_CONFIRM_THRESHOLD: f64 = 0.9;
+// variance every todo should get down to before the ranking counts as done
+const DEFAULT_TARGET_VARIANCE: f64 = 0.05;
+
+// where the todos are searched for, and where their comparisons and plots go
+#[derive(Clone)]
+struct Scope {
+    // what rg searches: "." or a single file
+    search: String,
+    log: String,
+    graph: String,
+    ci: String,
+}
+
+impl Scope {
+    fn dir() -> Scope {
+        Scope {
+            search: ".".into(),
+            log: "ratings.log".into(),
+            graph: "ratings_graph".into(),
+            ci: "ratings_ci.html".into(),
+        }
+    }
+
+    // one file in the current directory, with a log and plots of its own so
+    // that several files can sit side by side
+    fn file(name: &str) -> Scope {
+        Scope {
+            search: name.into(),
+            log: format!(".{}.ratings.log", name),
+            graph: format!("{}.ratings_graph", name),
+            ci: format!("{}.ratings_ci.html", name),
+        }
+    }
+}
+
+struct Config {
+    dir: String,
+    scope: Scope,
+    // when set, ask before recording a choice the model gave at least this
+    // probability of going the other way
+    confirm_threshold: Option<f64>,
+    // ask this many comparisons in one go instead of looping forever
+    batch_mode: Option<usize>,
+    // stop the loop after this many answers, since judgments get sloppier
+    // the longer a session runs
+    max_per_session: Option<usize>,
+    strategy: PairStrategy,
+    // give up on a prompt after this long and log the pair as skipped
+    comparison_timeout_secs: Option<u64>,
+    // print the current rankings as JSON and exit
+    export_json: bool,
+    // print how settled the ranking is and exit
+    stats: bool,
+    // describe each comparison (and any rid assigned for it) as a JSON line
+    // instead of text, for editor plugins and other tooling; the key prompts
+    // and status messages go to stderr so stdout is only JSON
+    json: bool,
+    // `sorter plot`: just regenerate the plots and exit
+    plot_only: bool,
+    // don't draw the comparison graph (graphviz) / the confidence intervals
+    skip_ratings_plot: bool,
+    skip_ci_plot: bool,
+}
+
+impl Config {
+    fn from_args() -> io::Result<Config> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+        let mut dir = None;
+        let mut file = None;
+        let mut confirm_threshold = None;
+        let mut batch_mode = None;
+        let mut max_per_session = None;
+        let mut strategy = PairStrategy::default();
+        let mut comparison_timeout_secs = None;
+        let mut export_json = false;
+        let mut stats = false;
+        let mut json = false;
+        let mut plot_only = false;
+        let mut skip_ratings_plot = false;
+        let mut skip_ci_plot = false;
+        let mut args = std::env::args().skip(1).peekable();
+        if args.peek().map(String::as_str) == Some("plot") {
+            args.next();
+            plot_only = true;
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dir" => {
+                    dir = Some(args.next().ok_or_else(|| invalid("--dir needs a path".into()))?);
+                }
+                "--file" => {
+                    file = Some(args.next().ok_or_else(|| invalid("--file needs a path".into()))?);
+                }
+                "--confirm-contradictions" => {
+                    confirm_threshold.get_or_insert(DEFAULT_CONFIRM_THRESHOLD);
+                }
+                "--confirm-threshold" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| invalid("--confirm-threshold needs a value".into()))?;
+                    let threshold: f64 = value
+                        .parse()
+                        .ok()
+                        .filter(|t| (0.5..=1.0).contains(t))
+                        .ok_or_else(|| invalid(format!("invalid threshold: {}", value)))?;
+                    confirm_threshold = Some(threshold);
+                }
+                "--batch-mode" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| invalid("--batch-mode needs a count".into()))?;
+                    let count = value
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid batch size: {}", value)))?;
+                    batch_mode = Some(count);
+                }
+                "--max-per-session" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| invalid("--max-per-session needs a count".into()))?;
+                    let count = value
+                        .parse()
+                        .ok()
+                        .filter(|&count| count > 0)
+                        .ok_or_else(|| invalid(format!("invalid session size: {}", value)))?;
+                    max_per_session = Some(count);
+                }
+                "--strategy" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| invalid("--strategy needs a value".into()))?;
+                    strategy = value.parse().map_err(invalid)?;
+                }
+                "--timeout" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| invalid("--timeout needs a number of seconds".into()))?;
+                    let secs = value
+                        .parse()
+                        .ok()
+                        .filter(|&secs| secs > 0)
+                        .ok_or_else(|| invalid(format!("invalid timeout: {}", value)))?;
+                    comparison_timeout_secs = Some(secs);
+                }
+                "--export-json" => export_json = true,
+                "--stats" => stats = true,
+                "--json" => json = true,
+                "--no-plot" => {
+                    skip_ratings_plot = true;
+                    skip_ci_plot = true;
+                }
+                "--no-ratings-plot" => skip_ratings_plot = true,
+                "--no-ci-plot" => skip_ci_plot = true,
+                _ if arg.starts_with("--") => return Err(invalid(format!("unknown flag: {}", arg))),
+                _ => dir = Some(arg),
+            }
+        }
+
+        let (dir, scope) = match (dir, file) {
+            (None, Some(file)) => {
+                let path = std::path::Path::new(&file);
+                let name = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| invalid(format!("invalid file: {}", file)))?;
+                let parent = path
+                    .parent()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or_else(|| ".".into());
+                (parent, Scope::file(name))
+            }
+            (Some(_), Some(_)) => return Err(invalid("pass either a directory or --file".into())),
+            (dir, None) => (
+                dir.ok_or_else(|| invalid("usage: sorter [plot] <dir | --file <file>> [flags]".into()))?,
+                Scope::dir(),
+            ),
+        };
+
+        Ok(Config {
+            dir,
+            scope,
+            confirm_threshold,
+            batch_mode,
+            max_per_session,
+            strategy,
+            comparison_timeout_secs,
+            export_json,
+            stats,
+            json,
+            plot_only,
+            skip_ratings_plot,
+            skip_ci_plot,
+        })
+    }
+
+    // where prompts and status messages go
+    fn term(&self) -> console::Term {
+        if self.json {
+            console::Term::stderr()
+        } else {
+            console::Term::stdout()
+        }
+    }
+}
 
 pub fn main() -> io::Result<()> {
-    let mut args = std::env::args();
-    args.next();
-    let dir = args.next().unwrap();
+    let mut config = Config::from_args()?;
+    let dir = config.dir.clone();
     std::env::set_current_dir(dir.clone())?;
-    println!(
-        "dir: {} (should be = {})",
-        std::env::current_dir()?.display(),
-        dir
-    );
-    loop {
-        run()?
+    if config.export_json {
+        return export_json(&config.scope);
+    }
+    if config.stats {
+        return print_stats(&config.scope);
+    }
+    if !config.skip_ratings_plot && !graphviz_installed() {
+        eprintln!("warning: graphviz's `dot` not found, not drawing the ratings graph");
+        config.skip_ratings_plot = true;
+    }
+    if config.plot_only {
+        return plot(&config);
+    }
+    if !config.json {
+        println!(
+            "dir: {} (should be = {})",
+            std::env::current_dir()?.display(),
+            dir
+        );
+    }
+    if let Some(count) = config.batch_mode {
+        return run_batch(&config, count);
+    }
+    let Some(max) = config.max_per_session else {
+        loop {
+            run(&config)?;
+        }
+    };
+
+    // mark where the session starts, so later analysis can tell early
+    // answers from late ones
+    let started = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    writeln!(open_ratings_log(&config.scope)?, "# session {}", started)?;
+    let mut answered = 0;
+    while answered < max {
+        if run(&config)? {
+            answered += 1;
+        }
+    }
+    if !config.json {
+        if let Some(session) = Session::load(&config.scope)? {
+            session.print_rankings();
+        }
     }
+    config.term().write_line(&format!("{} comparisons this session, time for a break", answered))?;
+    Ok(())
 }
 
-fn run() -> io::Result<()> {
-    let (mut with_rid, mut without_rid) = get_todos()?;
+// one pass of the main loop; returns whether an answer was recorded
+fn run(config: &Config) -> io::Result<bool> {
+    let Some(mut session) = Session::load(&config.scope)? else {
+        return Ok(false);
+    };
+    session.pick_pair(config.strategy);
+    session.spawn_plots(config);
+    let answer = session.compare(config)?;
+    // the next pass re-solves anyway; only solve here to see what moved
+    if let (Some((winner, loser)), Some(_)) = (answer, audit::path_from_env()) {
+        session.record(winner, loser);
+    }
+    Ok(answer.is_some())
+}
 
-    if without_rid.is_empty() && with_rid.is_empty() {
-        println!("No todos found");
+// ask for `count` comparisons against a single scan of the todos, feeding
+// each answer back into the model before picking the next pair
+fn run_batch(config: &Config, count: usize) -> io::Result<()> {
+    let Some(mut session) = Session::load(&config.scope)? else {
         return Ok(());
+    };
+    session.pick_pair(config.strategy);
+    for k in 1..=count {
+        if !config.json {
+            println!("[{}/{}]", k, count);
+        }
+        if let Some((winner, loser)) = session.compare(config)? {
+            session.update(config, winner, loser);
+        }
     }
 
-    let comparisons = if let Ok(file) = File::open("ratings.log") {
-        let fr = io::BufReader::new(&file);
-        fr.lines()
-            .filter_map(|line| {
-                let line = line.ok()?;
-                if let [i, j] = line
-                    .split(',')
-                    .filter_map(|s| s.parse().ok())
-                    .filter(|&i| with_rid.contains_key(&i))
-                    .collect::<Vec<_>>()[..]
-                {
-                    Some((i, j))
-                } else {
-                    None
+    for handle in session.spawn_plots(config) {
+        let _ = handle.join();
+    }
+    if !config.json {
+        session.print_rankings();
+    }
+    Ok(())
+}
+
+// regenerate ratings_graph.png and ratings_ci.html from ratings.log without
+// asking anything
+fn plot(config: &Config) -> io::Result<()> {
+    let Some(session) = Session::load(&config.scope)? else {
+        return Ok(());
+    };
+    for handle in session.spawn_plots(config) {
+        let _ = handle.join();
+    }
+    let scope = &config.scope;
+    match (config.skip_ratings_plot, config.skip_ci_plot) {
+        (false, false) => println!("regenerated {} and {}", scope.graph, scope.ci),
+        (false, true) => println!("regenerated {}", scope.graph),
+        (true, false) => println!("regenerated {}", scope.ci),
+        (true, true) => println!("nothing to regenerate"),
+    }
+    Ok(())
+}
+
+// same shape as the web service's RankedTask, plus the posterior variance
+#[derive(Serialize)]
+struct ExportedTask {
+    id: usize,
+    content: String,
+    completed: bool,
+    line: usize,
+    file: String,
+    score: f64,
+    variance: f64,
+    rank: usize,
+}
+
+fn export_json(scope: &Scope) -> io::Result<()> {
+    let tasks = match Session::load(scope)? {
+        Some(session) => session
+            .ranked()
+            .into_iter()
+            .enumerate()
+            .map(|(rank, (i, id))| {
+                let t = &session.with_rid[&id];
+                ExportedTask {
+                    id,
+                    content: t.todo.trim().to_string(),
+                    completed: false,
+                    line: t.line_num,
+                    file: t.file.clone(),
+                    score: session.ms_curr[i],
+                    variance: session.vs_curr[i],
+                    rank: rank + 1,
                 }
             })
-            .collect()
-    } else {
-        Vec::new()
+            .collect(),
+        None => Vec::new(),
     };
+    println!("{}", serde_json::to_string_pretty(&tasks)?);
+    Ok(())
+}
 
-    let mut id_to_index: HashMap<_, _> = with_rid
-        .iter()
-        .enumerate()
-        .map(|(i, (&id, _))| (id, i))
-        .collect();
-    let mut index_to_id: HashMap<_, _> = id_to_index.iter().map(|(&k, &v)| (v, k)).collect();
-
-    let n = with_rid.len() + if without_rid.is_empty() { 0 } else { 1 };
-    let mut m = vec![vec![0; n]; n];
-    for &(i, j) in &comparisons {
-        if id_to_index.contains_key(&i) && id_to_index.contains_key(&j) {
-            m[id_to_index[&i]][id_to_index[&j]] += 1;
-        }
-    }
-    // println!("m: {:?}", m);
-    // println!("n: {}", n);
-
-    let mut asap = ASAP::new(n);
-
-    let (pair, prob, ms_curr, vs_curr) = asap.run_asap(&m);
-
-    {
-        let id_to_index = id_to_index.clone();
-        let prob = prob.clone();
-        let ms_curr = ms_curr.clone();
-        thread::spawn(move || {
-            crate::plot_ratings::plot_ratings(
-                "ratings.log",
-                "ratings_graph",
-                &ms_curr,
-                &id_to_index,
-                &prob,
-            )
-            .unwrap();
-        });
+fn print_stats(scope: &Scope) -> io::Result<()> {
+    if let Some(session) = Session::load(scope)? {
+        println!(
+            "Confidence: {:.0}% (target variance {})",
+            session.asap.percent_confident(DEFAULT_TARGET_VARIANCE),
+            DEFAULT_TARGET_VARIANCE
+        );
     }
+    Ok(())
+}
+
+// one line of `--json` output per comparison
+#[derive(Serialize)]
+struct ComparisonJson<'a> {
+    // rids written to files for this comparison
+    assigned: Vec<AssignedRid<'a>>,
+    pair: [PairTodo<'a>; 2],
+    // model's estimate that the first todo wins
+    p_first: f64,
+}
 
-    {
-        let index_to_id = index_to_id.clone();
-        let ms_curr = ms_curr.clone();
-        let vs_curr = vs_curr.clone();
-        let with_rid = with_rid.clone();
-        thread::spawn(move || {
-            let items: Vec<_> = ms_curr
-                .iter()
-                .zip(vs_curr.iter())
-                .enumerate()
-                .filter_map(|(i, (&m, &v))| {
-                    index_to_id
-                        .get(&i)
-                        .and_then(|&id| with_rid.get(&id).map(|t| (t.todo.clone(), m, v)))
+#[derive(Serialize)]
+struct AssignedRid<'a> {
+    rid: usize,
+    file: &'a str,
+    line: usize,
+}
+
+#[derive(Serialize)]
+struct PairTodo<'a> {
+    id: usize,
+    content: &'a str,
+    file: &'a str,
+    line: usize,
+}
+
+// todos from one scan plus the model solved over them
+struct Session {
+    scope: Scope,
+    with_rid: HashMap<usize, Todo>,
+    without_rid: Vec<Todo>,
+    id_to_index: HashMap<usize, usize>,
+    index_to_id: HashMap<usize, usize>,
+    n: usize,
+    m: Vec<Vec<i32>>,
+    asap: ASAP,
+    pair: (usize, usize),
+    prob: Vec<Vec<f64>>,
+    ms_curr: Vec<f64>,
+    vs_curr: Vec<f64>,
+}
+
+impl Session {
+    fn load(scope: &Scope) -> io::Result<Option<Session>> {
+        let (with_rid, without_rid) = get_todos(&scope.search)?;
+
+        if without_rid.is_empty() && with_rid.is_empty() {
+            eprintln!("No todos found");
+            return Ok(None);
+        }
+
+        let comparisons = if let Ok(file) = File::open(&scope.log) {
+            let fr = io::BufReader::new(&file);
+            fr.lines()
+                .filter_map(|line| {
+                    let line = line.ok()?;
+                    // skipped (timed out) pairs carry no preference
+                    if line.ends_with(",skip") {
+                        return None;
+                    }
+                    if let [i, j] = line
+                        .split(',')
+                        .filter_map(|s| s.parse().ok())
+                        .filter(|&i| with_rid.contains_key(&i))
+                        .collect::<Vec<_>>()[..]
+                    {
+                        Some((i, j))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let id_to_index: HashMap<_, _> = with_rid
+            .iter()
+            .enumerate()
+            .map(|(i, (&id, _))| (id, i))
+            .collect();
+        let index_to_id: HashMap<_, _> = id_to_index.iter().map(|(&k, &v)| (v, k)).collect();
+
+        // the last index stands in for a not yet tracked todo, if any
+        let n = with_rid.len() + if without_rid.is_empty() { 0 } else { 1 };
+        let mut m = vec![vec![0; n]; n];
+        for &(i, j) in &comparisons {
+            if id_to_index.contains_key(&i) && id_to_index.contains_key(&j) {
+                m[id_to_index[&i]][id_to_index[&j]] += 1;
+            }
+        }
+        // println!("m: {:?}", m);
+        // println!("n: {}", n);
+
+        let mut asap = ASAP::new(n);
+
+        let (prob, ms_curr, vs_curr) = asap.run_solver(&m);
+
+        Ok(Some(Session {
+            scope: scope.clone(),
+            with_rid,
+            without_rid,
+            id_to_index,
+            index_to_id,
+            n,
+            m,
+            asap,
+            pair: (0, 0),
+            prob,
+            ms_curr,
+            vs_curr,
+        }))
+    }
+
+    // add one new comparison (by solver index) to the model and re-pick the pair
+    fn update(&mut self, config: &Config, winner: usize, loser: usize) {
+        self.record(winner, loser);
+        self.pick_pair(config.strategy);
+    }
+
+    // add one new comparison (by solver index) to the model, and to the audit
+    // log if AUDIT_LOG is set
+    fn record(&mut self, winner: usize, loser: usize) {
+        self.m[winner][loser] += 1;
+        let mut delta = vec![vec![0; self.n]; self.n];
+        delta[winner][loser] = 1;
+        let before = self.ms_curr.clone();
+        (self.prob, self.ms_curr, self.vs_curr) = self.asap.run_solver(&delta);
+
+        if let Some(path) = audit::path_from_env() {
+            let items: Vec<Option<(usize, &str)>> = (0..self.n)
+                .map(|i| {
+                    let id = *self.index_to_id.get(&i)?;
+                    Some((id, self.with_rid[&id].todo.trim()))
                 })
                 .collect();
-            plot_ci(items, "ratings_ci.html").unwrap();
-        });
+            let changes = audit::score_changes(&items, &before, &self.ms_curr, [winner, loser]);
+            let ids = [self.index_to_id[&winner], self.index_to_id[&loser]];
+            if let Err(e) = audit::append(&path, ids, ids[0], &changes) {
+                eprintln!("warning: failed to write the audit log: {}", e);
+            }
+        }
     }
 
-    // assign an id / add [[rid::]] to a random todo in without_rid
-    if !without_rid.is_empty() && (pair.0 == n - 1 || pair.1 == n - 1) {
-        let ix = n - 1;
-        let rid = with_rid.iter().map(|(id, _)| id).max().unwrap_or(&0usize) + 1;
-        let mut rng = rand::thread_rng();
-        let idx = rng.gen_range(0..without_rid.len());
-        let todo = without_rid.swap_remove(idx);
-        let new_line = if let Some(pos) = EMOJIS.iter().filter_map(|e| todo.todo.find(e)).min() {
-            format!(
-                "{} [[rid::{}]] {}",
-                &todo.todo[..pos],
-                rid,
-                &todo.todo[pos..]
-            )
+    fn pick_pair(&mut self, strategy: PairStrategy) {
+        let insertion = match strategy {
+            PairStrategy::Insertion => {
+                // rids are handed out incrementally, and an untracked todo is newer still
+                let mut order: Vec<(usize, usize)> =
+                    self.index_to_id.iter().map(|(&i, &id)| (id, i)).collect();
+                order.sort_unstable();
+                let mut order: Vec<usize> = order.into_iter().map(|(_, i)| i).collect();
+                if order.len() < self.n {
+                    order.push(self.n - 1);
+                }
+                insertion_pair(&order, &self.ms_curr, &self.m)
+            }
+            PairStrategy::Eig => None,
+        };
+        self.pair = insertion
+            .unwrap_or_else(|| self.asap.optimal_pair(&self.prob, &self.ms_curr, &self.vs_curr));
+    }
+
+    fn spawn_plots(&self, config: &Config) -> Vec<thread::JoinHandle<()>> {
+        let mut handles = Vec::new();
+
+        if !config.skip_ratings_plot {
+            let id_to_index = self.id_to_index.clone();
+            let prob = self.prob.clone();
+            let ms_curr = self.ms_curr.clone();
+            let scope = self.scope.clone();
+            handles.push(thread::spawn(move || {
+                crate::plot_ratings::plot_ratings(
+                    &scope.log,
+                    &scope.graph,
+                    &ms_curr,
+                    &id_to_index,
+                    &prob,
+                )
+                .unwrap();
+            }));
+        }
+
+        if !config.skip_ci_plot {
+            let index_to_id = self.index_to_id.clone();
+            let ms_curr = self.ms_curr.clone();
+            let vs_curr = self.vs_curr.clone();
+            let with_rid = self.with_rid.clone();
+            let ci = self.scope.ci.clone();
+            let counts: Vec<usize> = (0..self.n)
+                .map(|i| {
+                    (0..self.n)
+                        .filter(|&j| j != i)
+                        .map(|j| (self.m[i][j] + self.m[j][i]) as usize)
+                        .sum()
+                })
+                .collect();
+            handles.push(thread::spawn(move || {
+                let items: Vec<_> = ms_curr
+                    .iter()
+                    .zip(vs_curr.iter())
+                    .enumerate()
+                    .filter_map(|(i, (&m, &v))| {
+                        index_to_id.get(&i).and_then(|&id| {
+                            with_rid.get(&id).map(|t| (t.todo.clone(), m, v, counts[i]))
+                        })
+                    })
+                    .collect();
+                plot_ci(items, &ci).unwrap();
+            }));
+        }
+
+        handles
+    }
+
+    // show the current pair and record the answer in ratings.log; returns
+    // the (winner, loser) solver indices if something was recorded
+    fn compare(&mut self, config: &Config) -> io::Result<Option<(usize, usize)>> {
+        let pair = self.pair;
+
+        // assign an id / add [[rid::]] to a random todo in without_rid
+        let mut assigned = None;
+        let ix = self.n - 1;
+        if !self.index_to_id.contains_key(&ix) && (pair.0 == ix || pair.1 == ix) {
+            // every untracked todo was skipped below, earlier in this batch
+            if self.without_rid.is_empty() {
+                return Ok(None);
+            }
+            let rid = self.with_rid.keys().max().unwrap_or(&0usize) + 1;
+            let mut rng = rand::thread_rng();
+            let idx = rng.gen_range(0..self.without_rid.len());
+            let todo = self.without_rid.swap_remove(idx);
+            let new_line = if let Some(pos) = EMOJIS.iter().filter_map(|e| todo.todo.find(e)).min() {
+                format!(
+                    "{} [[rid::{}]] {}",
+                    &todo.todo[..pos],
+                    rid,
+                    &todo.todo[pos..]
+                )
+            } else {
+                format!("{} [[rid::{}]]", todo.todo, rid)
+            };
+            if !replace_line_in_file(&todo.file, todo.line_num, &todo.todo, &new_line)? {
+                eprintln!(
+                    "warning: {}:{} changed since it was scanned, skipping this comparison",
+                    todo.file, todo.line_num
+                );
+                return Ok(None);
+            }
+            self.with_rid.insert(rid, todo.clone());
+            self.index_to_id.insert(ix, rid);
+            self.id_to_index.insert(rid, ix);
+            assigned = Some(rid);
+        }
+
+        // model's current estimate that the first item beats the second
+        let p_first = self.prob[pair.0][pair.1];
+
+        let ids = pair.map(|i| *self.index_to_id.get(&i).unwrap());
+        if config.json {
+            let todo = |id| {
+                let t = &self.with_rid[&id];
+                PairTodo { id, content: t.todo.trim(), file: &t.file, line: t.line_num }
+            };
+            let line = ComparisonJson {
+                assigned: assigned
+                    .map(|rid| {
+                        let t = &self.with_rid[&rid];
+                        AssignedRid { rid, file: &t.file, line: t.line_num }
+                    })
+                    .into_iter()
+                    .collect(),
+                pair: [todo(ids.0), todo(ids.1)],
+                p_first,
+            };
+            println!("{}", serde_json::to_string(&line)?);
         } else {
-            format!("{} [[rid::{}]]", todo.todo, rid)
+            ids.for_each(|id| {
+                let t = self.with_rid.get(&id).unwrap();
+                println!("{} ({}:{})", t.todo, t.file, t.line_num);
+            });
+            if p_first >= 0.5 {
+                println!("(model thinks #1 wins {:.0}%)", p_first * 100.0);
+            } else {
+                println!("(model thinks #2 wins {:.0}%)", (1.0 - p_first) * 100.0);
+            }
+        }
+
+        let timeout = config.comparison_timeout_secs;
+        let term = config.term();
+        let Some(c) = prompt_key(&term, "Enter 1 or 2", timeout)? else {
+            term.write_line("Timed out, skipping")?;
+            writeln!(open_ratings_log(&self.scope)?, "{},{},skip", ids.0, ids.1)?;
+            return Ok(None);
         };
-        with_rid.insert(rid, todo.clone());
-        index_to_id.insert(ix, rid);
-        id_to_index.insert(rid, ix);
-        replace_line_in_file(&todo.file, todo.line_num, &new_line)?;
-    }
-
-    let pair = pair.map(|i| index_to_id.get(&i).unwrap());
-    pair.for_each(|id| {
-        let t = with_rid.get(id).unwrap();
-        println!("{} ({}:{})", t.todo, t.file, t.line_num);
-    });
-
-    print!("Enter 1 or 2: ");
-    io::stdout().flush()?;
-    let c = console::Term::stdout().read_char()?;
-    println!();
-    let mut file = if let Ok(file) = File::options().append(true).open("ratings.log") {
-        file
+
+        // catch fat-fingered answers that go against a confident prediction
+        let p_chosen = if c == '1' { p_first } else { 1.0 - p_first };
+        if let Some(threshold) = config.confirm_threshold {
+            if 1.0 - p_chosen >= threshold {
+                let prompt = format!(
+                    "model strongly disagrees (#{} favored {:.0}%), confirm? [y/N]",
+                    if c == '1' { 2 } else { 1 },
+                    (1.0 - p_chosen) * 100.0
+                );
+                let confirm = prompt_key(&term, &prompt, timeout)?;
+                if !matches!(confirm, Some('y' | 'Y')) {
+                    term.write_line("Not recorded")?;
+                    return Ok(None);
+                }
+            }
+        }
+        writeln!(
+            open_ratings_log(&self.scope)?,
+            "{},{}",
+            if c == '1' { ids.0 } else { ids.1 },
+            if c == '1' { ids.1 } else { ids.0 }
+        )?;
+
+        Ok(Some(if c == '1' { pair } else { (pair.1, pair.0) }))
+    }
+
+    // (solver index, rid) of every tracked todo, best first
+    fn ranked(&self) -> Vec<(usize, usize)> {
+        let mut ranked: Vec<_> = self.index_to_id.iter().map(|(&i, &id)| (i, id)).collect();
+        ranked.sort_by(|a, b| self.ms_curr[b.0].partial_cmp(&self.ms_curr[a.0]).unwrap());
+        ranked
+    }
+
+    fn print_rankings(&self) {
+        println!("Rankings:");
+        for (rank, (i, id)) in self.ranked().into_iter().enumerate() {
+            let t = &self.with_rid[&id];
+            println!("{:>3}. {:>6.2}  {}", rank + 1, self.ms_curr[i], t.todo.trim());
+        }
+    }
+}
+
+// whether graphviz's `dot`, which draws the ratings graph, is on the PATH
+fn graphviz_installed() -> bool {
+    Command::new("dot")
+        .arg("-V")
+        .output()
+        .is_ok_and(|out| out.status.success())
+}
+
+fn open_ratings_log(scope: &Scope) -> io::Result<File> {
+    if let Ok(file) = File::options().append(true).open(&scope.log) {
+        Ok(file)
     } else {
-        File::create("ratings.log")?
+        File::create(&scope.log)
+    }
+}
+
+// Show `prompt` on `term` and wait for a key press. With a timeout, a
+// countdown is shown and None is returned if it runs out.
+fn prompt_key(term: &console::Term, prompt: &str, timeout_secs: Option<u64>) -> io::Result<Option<char>> {
+    let mut out = term;
+    let Some(timeout_secs) = timeout_secs else {
+        write!(out, "{}: ", prompt)?;
+        out.flush()?;
+        let c = term.read_char()?;
+        term.write_line("")?;
+        return Ok(Some(c));
     };
-    writeln!(
-        file,
-        "{},{}",
-        if c == '1' { pair.0 } else { pair.1 },
-        if c == '1' { pair.1 } else { pair.0 }
-    )?;
 
-    Ok(())
+    let keys = key_reader(term).lock().unwrap();
+    for remaining in (1..=timeout_secs).rev() {
+        write!(out, "\r{} ({}s): ", prompt, remaining)?;
+        out.flush()?;
+        match keys.recv_timeout(Duration::from_secs(1)) {
+            Ok(c) => {
+                term.write_line("")?;
+                return c.map(Some);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "key reader stopped"));
+            }
+        }
+    }
+    term.write_line("")?;
+    Ok(None)
+}
+
+// read_char can't be cancelled, so timed prompts share one reader thread
+// rather than leaving a stray read behind to swallow the next key press. The
+// thread reads through the `term` of the first timed prompt.
+fn key_reader(term: &console::Term) -> &'static Mutex<mpsc::Receiver<io::Result<char>>> {
+    static KEYS: OnceLock<Mutex<mpsc::Receiver<io::Result<char>>>> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        let term = term.clone();
+        thread::spawn(move || loop {
+            let c = term.read_char();
+            let failed = c.is_err();
+            if tx.send(c).is_err() || failed {
+                break;
+            }
+        });
+        Mutex::new(rx)
+    })
 }
 
 #[derive(Clone)]
@@ -167,13 +795,15 @@ struct Todo {
     todo: String,
 }
 
-fn get_todos() -> io::Result<(HashMap<usize, Todo>, Vec<Todo>)> {
+// `search` is a directory or a single file; -H keeps the file name in the
+// output for the latter too
+fn get_todos(search: &str) -> io::Result<(HashMap<usize, Todo>, Vec<Todo>)> {
     let command_output = Command::new("rg")
-        .args(&[r"^\s*- \[ \]", ".", "-n"])
+        .args([r"^\s*- \[ \]", search, "-n", "-H"])
         .output()?;
     let output = String::from_utf8_lossy(&command_output.stdout);
     if !command_output.status.success() {
-        println!("Error running rg, is it installed?");
+        eprintln!("Error running rg, is it installed?");
     }
 
     let mut with_rid = HashMap::new();
@@ -194,10 +824,10 @@ fn get_todos() -> io::Result<(HashMap<usize, Todo>, Vec<Todo>)> {
                     if let Ok(rid) = line[start + 7..start + end].parse() {
                         with_rid.insert(rid, todo);
                     } else {
-                        println!("Invalid rid: {}", &line[start + 7..start + end]);
+                        eprintln!("Invalid rid: {}", &line[start + 7..start + end]);
                     }
                 } else {
-                    println!("Invalid rid: {}", &line[start..]);
+                    eprintln!("Invalid rid: {}", &line[start..]);
                 }
             } else {
                 without_rid.push(todo);
@@ -208,16 +838,74 @@ fn get_todos() -> io::Result<(HashMap<usize, Todo>, Vec<Todo>)> {
     Ok((with_rid, without_rid))
 }
 
-fn replace_line_in_file(file: &str, line_num: usize, new_content: &str) -> io::Result<()> {
+// Replace line `line_num` (1-based) with `new_content` if it still reads
+// `expected`, keeping the file's line endings (\n or \r\n) and whether it
+// ended with a newline. Returns false, leaving the file alone, if the file
+// was edited since it was scanned and the line moved or is gone.
+fn replace_line_in_file(
+    file: &str,
+    line_num: usize,
+    expected: &str,
+    new_content: &str,
+) -> io::Result<bool> {
     let content = std::fs::read_to_string(file)?;
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
     let mut lines: Vec<String> = content.lines().map(String::from).collect();
-    if line_num > lines.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("line {} is out of bounds", line_num),
-        ));
+    if line_num == 0 || lines.get(line_num - 1).map(String::as_str) != Some(expected) {
+        return Ok(false);
     }
     lines[line_num - 1] = new_content.to_string();
-    std::fs::write(file, lines.join("\n"))?;
-    Ok(())
+    let mut new_file = lines.join(newline);
+    if content.ends_with('\n') {
+        new_file.push_str(newline);
+    }
+    std::fs::write(file, new_file)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn replace_in(content: &str, line_num: usize, new_content: &str) -> String {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        let path = file.path().to_str().unwrap();
+        let expected = content.lines().nth(line_num - 1).unwrap();
+        assert!(replace_line_in_file(path, line_num, expected, new_content).unwrap());
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn replace_line_keeps_trailing_newline() {
+        assert_eq!(replace_in("a\nb\nc\n", 3, "C"), "a\nb\nC\n");
+        assert_eq!(replace_in("a\nb\nc", 2, "B"), "a\nB\nc");
+    }
+
+    #[test]
+    fn replace_line_keeps_crlf() {
+        assert_eq!(replace_in("a\r\nb\r\n", 1, "A"), "A\r\nb\r\n");
+        assert_eq!(replace_in("a\r\nb", 2, "B"), "a\r\nB");
+    }
+
+    #[test]
+    fn replace_line_out_of_bounds() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"a\n").unwrap();
+        let path = file.path().to_str().unwrap();
+        assert!(!replace_line_in_file(path, 0, "a", "x").unwrap());
+        assert!(!replace_line_in_file(path, 2, "a", "x").unwrap());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "a\n");
+    }
+
+    #[test]
+    fn replace_line_skips_edited_line() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"new line\n- [ ] todo\n").unwrap();
+        let path = file.path().to_str().unwrap();
+        // scanned as line 1 before "new line" was inserted above it
+        assert!(!replace_line_in_file(path, 1, "- [ ] todo", "x").unwrap());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "new line\n- [ ] todo\n");
+    }
 }
@@ -1,41 +1,241 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
+    error_handling::HandleErrorLayer,
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
     response::IntoResponse,
     routing::{get, post},
-    Json, Router,
+    BoxError, Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower::ServiceBuilder;
+use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tokio::net::TcpListener;
-use crate::asap_cpu::ASAP;
+use crate::asap_cpu::{group_comparisons, insertion_pair, PairStrategy, TrueSkillConfig, ASAP};
+use crate::audit;
+use crate::baselines;
+use crate::plot_ci::{plot_ci_with_options, PlotOptions};
+use crate::similarity::{duplicate_clusters, SimilarityMetric};
+use crate::webhook::{notify_converged, WebhookConfig};
+
+// Posterior variance at which a task counts as confidently placed
+const DEFAULT_TARGET_VARIANCE: f64 = 0.05;
+// How many tasks the embeddable rankings show by default, and at most
+const DEFAULT_EMBED_LIMIT: usize = 10;
+const MAX_EMBED_LIMIT: usize = 100;
+// Most task contents a single batch lookup may ask for
+const MAX_BATCH_LOOKUP: usize = 100;
+// All-pairs probabilities grow quadratically, so larger lists need task_ids
+const MAX_PROBABILITY_TASKS: usize = 100;
+// Default per-request timeout, overridable with REQUEST_TIMEOUT_SECS
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 15;
+// Above this many tasks the full EIG pair search is skipped in favour of the
+// cheaper uncompared-pairs heuristic, overridable with EIG_MAX_TASKS
+const DEFAULT_EIG_MAX_TASKS: usize = 50;
+// Above this many tasks the uncompared-pairs heuristic only looks at each
+// task's nearest PAIR_SEARCH_WINDOW neighbours by score, not all n^2 pairs
+const WINDOWED_PAIR_SEARCH_MIN_TASKS: usize = 500;
+const PAIR_SEARCH_WINDOW: usize = 2;
+// Default cap on the number of tasks, overridable with MAX_TASKS_PER_LIST.
+// The solver's comparison matrix is n^2, so this bounds memory and latency.
+const DEFAULT_MAX_TASKS_PER_LIST: usize = 2000;
+// Largest magnitude accepted for a score set by hand. Solved scores sit
+// within a few prior standard deviations (~7) of 0, so anything beyond this
+// comes from some other scale and would swamp the comparisons.
+const MAX_ABS_SCORE: f64 = 50.0;
+// Largest per-comparison draw margin. Performances vary with a standard
+// deviation of about 1, so this already asks for a win by ten of them.
+const MAX_DRAW_MARGIN: f64 = 10.0;
+// How many of the latest comparisons rank_stability is averaged over
+const RANK_STABILITY_WINDOW: usize = 10;
+// Rank spread across models beyond which a task counts as controversial
+const CONTROVERSIAL_RANK_SPREAD: usize = 2;
+// A task is suggested for removal once it has lost at least this share of
+// at least this many comparisons and ranks in the bottom quarter
+const REMOVAL_MIN_COMPARISONS: usize = 3;
+const REMOVAL_MAX_WIN_RATE: f64 = 0.1;
+// Pairs returned by the rankings heatmap unless heatmap_k says otherwise
+const DEFAULT_HEATMAP_K: usize = 20;
+// Most tasks one group ranking may order (it expands to n(n-1)/2 comparisons)
+const MAX_GROUP_SIZE: usize = 20;
+// Minimum similarity for two tasks to be reported as likely duplicates
+const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.75;
+// rank_stability below which the list counts as converged, overridable with
+// CONVERGENCE_THRESHOLD
+const DEFAULT_CONVERGENCE_THRESHOLD: f64 = 0.1;
+// Minimum time between two convergence notifications
+const CONVERGENCE_NOTIFY_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+// How many of the top tasks the convergence notification lists
+const CONVERGENCE_NOTIFY_TOP: usize = 5;
 
 // Type for storing our application state
 pub struct AppState {
     tasks: Mutex<HashMap<usize, TaskInfo>>,
     comparisons: Mutex<Vec<Comparison>>,
+    // (prerequisite_id, dependent_id): the dependent can't rank above its
+    // prerequisite while the prerequisite is unfinished
+    dependencies: Mutex<Vec<(usize, usize)>>,
+    ranking_config: RankingConfig,
+    max_tasks: usize,
+    // Slack / Discord webhooks to tell when the list has converged, if any
+    webhook: Option<WebhookConfig>,
+    last_notified_at: Mutex<Option<Instant>>,
+    // Score that tasks tagged #tag start from, before comparisons. Lock
+    // after `tasks` when holding both.
+    tag_priors: Mutex<HashMap<String, f64>>,
+    // Where to log how each comparison moved the scores, if anywhere
+    audit_log: Option<String>,
+    // When comparisons were last removed, or the server started (losing
+    // them all); a delta sync from before this has to start over. Lock
+    // after `comparisons`.
+    comparisons_removed_at: Mutex<chrono::DateTime<chrono::Utc>>,
+}
+
+impl AppState {
+    // Note that comparisons were removed, while still holding their lock so
+    // GET /comparisons sees both changes together
+    fn comparisons_removed(&self) {
+        *self.comparisons_removed_at.lock().unwrap() = chrono::Utc::now();
+    }
+}
+
+// Builds an `AppState`, so new fields get a default or a check in one place
+// instead of at every construction site
+#[derive(Default)]
+pub struct AppStateBuilder {
+    ranking_config: Option<RankingConfig>,
+    max_tasks: Option<usize>,
+    webhook: Option<WebhookConfig>,
+    audit_log: Option<String>,
+}
+
+impl AppStateBuilder {
+    pub fn ranking_config(mut self, ranking_config: RankingConfig) -> Self {
+        self.ranking_config = Some(ranking_config);
+        self
+    }
+
+    pub fn max_tasks(mut self, max_tasks: usize) -> Self {
+        self.max_tasks = Some(max_tasks);
+        self
+    }
+
+    pub fn webhook(mut self, webhook: Option<WebhookConfig>) -> Self {
+        self.webhook = webhook;
+        self
+    }
+
+    pub fn audit_log(mut self, path: Option<String>) -> Self {
+        self.audit_log = path;
+        self
+    }
+
+    // Fails if a required field is missing or a setting can't work
+    pub fn build(self) -> Result<Arc<AppState>, String> {
+        let ranking_config = self.ranking_config.ok_or("ranking_config is required")?;
+        let max_tasks = self.max_tasks.unwrap_or(DEFAULT_MAX_TASKS_PER_LIST);
+        if max_tasks == 0 {
+            return Err("max_tasks must be at least 1".to_string());
+        }
+        Ok(Arc::new(AppState {
+            tasks: Mutex::new(HashMap::new()),
+            comparisons: Mutex::new(Vec::new()),
+            dependencies: Mutex::new(Vec::new()),
+            ranking_config,
+            max_tasks,
+            webhook: self.webhook,
+            last_notified_at: Mutex::new(None),
+            tag_priors: Mutex::new(HashMap::new()),
+            audit_log: self.audit_log,
+            comparisons_removed_at: Mutex::new(chrono::Utc::now()),
+        }))
+    }
+}
+
+// Server-wide solver settings, read from the environment at startup
+#[derive(Debug, Clone, Copy)]
+pub struct RankingConfig {
+    eig_max_tasks: usize,
+    trueskill: TrueSkillConfig,
+    convergence_threshold: f64,
+}
+
+impl RankingConfig {
+    fn from_env() -> Self {
+        fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default)
+        }
+
+        let defaults = TrueSkillConfig::default();
+        RankingConfig {
+            eig_max_tasks: env_or("EIG_MAX_TASKS", DEFAULT_EIG_MAX_TASKS),
+            trueskill: TrueSkillConfig {
+                beta_sq: env_or("TRUESKILL_BETA_SQ", defaults.beta_sq),
+                rare_item_beta_sq: env_or("TRUESKILL_RARE_ITEM_BETA_SQ", defaults.rare_item_beta_sq),
+                prior_precision: env_or("TRUESKILL_PRIOR_PRECISION", defaults.prior_precision),
+                incremental_updates: env_or("TRUESKILL_INCREMENTAL_UPDATES", defaults.incremental_updates),
+            },
+            convergence_threshold: env_or("CONVERGENCE_THRESHOLD", DEFAULT_CONVERGENCE_THRESHOLD),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TaskInfo {
     id: usize,
     content: String,
     completed: bool,
     line: usize,
     file: String,
+    created_at: String,
+    // Pinned score for a reference task (e.g. "something I'd do today"),
+    // which the solver never moves; comparisons against anchors put the
+    // other scores on a fixed scale
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    anchor_score: Option<f64>,
+    // Win rate carried over from another ranking system, which sets where
+    // the task's score starts before comparisons refine it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prior_win_rate: Option<f64>,
+    // Score to start from, e.g. from an existing priority ranking; takes
+    // precedence over prior_win_rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    initial_score: Option<f64>,
+    // #hashtags in the content, lowercased
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    // Mean of the tag priors set for `tags`, kept in sync by `tag_prior`
+    // callers
+    #[serde(skip)]
+    tag_prior: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Comparison {
     task_a_id: usize,
     task_b_id: usize,
     winner_id: usize,
     timestamp: String,
+    // How clearly the winner has to beat the loser in this comparison; None
+    // uses the solver's global margin of 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    draw_margin: Option<f64>,
+    // Synthesised by POST /import/ordered rather than answered
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    seeded: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct RankedTask {
     id: usize,
     content: String,
@@ -44,48 +244,550 @@ pub struct RankedTask {
     file: String,
     score: f64,
     rank: usize,
+    // How many of the other tasks this one has been compared against directly
+    direct_comparisons: usize,
+    possible_direct: usize,
 }
 
 // Requests and responses
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AddTaskRequest {
     content: String,
     completed: bool,
     line: usize,
     file: String,
+    #[serde(default)]
+    anchor_score: Option<f64>,
+    #[serde(default)]
+    initial_score: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AddTaskResponse {
+    #[serde(flatten)]
+    task: TaskInfo,
+    // Whether the task starts from initial_score rather than from the middle
+    initial_score_applied: bool,
 }
 
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AddComparisonRequest {
     task_a_id: usize,
     task_b_id: usize,
-    winner_id: usize,
+    // Exactly one of winner_id and winner
+    #[serde(default)]
+    winner_id: Option<usize>,
+    #[serde(default)]
+    winner: Option<Side>,
+    #[serde(default)]
+    draw_margin: Option<f64>,
+}
+
+// Which of the two compared tasks won, by position like the CLI's 1 / 2
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    A,
+    B,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AddGroupComparisonRequest {
+    // A full ordering of some tasks from one event, first place first
+    ordered_task_ids: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DependencyRequest {
+    prerequisite_id: usize,
+    dependent_id: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchLookupRequest {
+    contents: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ComparisonPartnersRequest {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairRequest {
+    task_a_content: String,
+    task_b_content: String,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairResponse {
+    // Direct comparisons between the two, in either order
+    comparisons: u32,
+    a_wins: u32,
+    b_wins: u32,
+    // Model's probability that task a beats task b
+    model_probability: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TagInfo {
+    tag: String,
+    tasks: usize,
+    prior_mean: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetTagPriorsRequest {
+    // Tag (without the #) -> score its tasks start from; null clears it
+    priors: HashMap<String, Option<f64>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetPriorBatchRequest {
+    // Task content -> historical win rate in [0, 1]
+    priors: HashMap<String, f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetPriorBatchResponse {
+    // Tasks whose prior was set; duplicates with the same content all are
+    updated: usize,
+    // Contents that matched no task
+    unknown: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ComparisonPartner {
+    id: usize,
+    content: String,
+    // Comparisons the requested task won / lost against this partner
+    wins_against: u32,
+    losses_against: u32,
+    win_rate: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportRatingsLogRequest {
+    // Contents of the CLI's ratings.log: one `winner_rid,loser_rid` per line
+    log_content: String,
+    // Maps the CLI's [[rid::N]] ids to task content
+    id_to_content: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportResult {
+    imported: usize,
+    skipped: usize,
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PruneRequest {
+    older_than_days: u32,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct RankingsQuery {
+    #[serde(default)]
+    strategy: PairStrategy,
+    // Only return the best / worst k tasks; ranks and stats still cover
+    // the whole list. With both set the two ends are concatenated.
+    top_k: Option<usize>,
+    bottom_k: Option<usize>,
+    // Order tied (e.g. never-compared) tasks newest first instead of oldest first
+    #[serde(default)]
+    newest_first: bool,
+    // Also return the most informative pairs to compare (two solver passes
+    // per candidate pair)
+    #[serde(default)]
+    include_heatmap: bool,
+    heatmap_k: Option<usize>,
+    // Comma-separated tags: rank only the tasks carrying any (or, with
+    // tags_match=all, all) of them, against each other
+    tags: Option<String>,
+    #[serde(default)]
+    tags_match: TagMatch,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+impl TagMatch {
+    fn matches(self, task_tags: &[String], wanted: &[String]) -> bool {
+        match self {
+            TagMatch::Any => wanted.iter().any(|tag| task_tags.contains(tag)),
+            TagMatch::All => wanted.iter().all(|tag| task_tags.contains(tag)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum EmbedTheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct EmbedQuery {
+    #[serde(default)]
+    theme: EmbedTheme,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ProbabilitiesQuery {
+    // Comma-separated task ids to restrict the pairs to; all tasks if unset
+    task_ids: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairProbabilityInfo {
+    task_a_id: usize,
+    task_b_id: usize,
+    // Model's probability that task a beats task b
+    win_probability: f64,
+    // 1 + v_a + v_b (with the configured beta^2 in place of 1); large when
+    // the probability rests on little data
+    variance: f64,
+    // win_probability with the skill difference at either end of its ~95%
+    // interval
+    win_probability_low: f64,
+    win_probability_high: f64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct SvgQuery {
+    #[serde(default)]
+    dark_mode: bool,
 }
 
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TasksResponse {
-    tasks: Vec<TaskInfo>,
+    tasks: Vec<TaskWithStats>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConsistencyResponse {
+    comparisons: usize,
+    // Comparisons whose winner ends up scored below the loser
+    contradictions: usize,
+    // contradictions / comparisons, 0 when there are none
+    contradiction_rate: f64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct TasksQuery {
+    // Also return each task's record and rank; runs the solver
+    #[serde(default)]
+    include_stats: bool,
+    // Only completed (true) or open (false) tasks
+    completed: Option<bool>,
+    // Only tasks created strictly after / before these RFC 3339 timestamps
+    created_after: Option<String>,
+    created_before: Option<String>,
+}
+
+impl TasksQuery {
+    // The filter these parameters describe, or an error naming the bad timestamp
+    fn filter(&self) -> Result<impl Fn(&TaskInfo) -> bool, String> {
+        let parse = |name: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(chrono::DateTime::parse_from_rfc3339)
+                .transpose()
+                .map_err(|_| format!("{} must be an RFC 3339 timestamp", name))
+        };
+        let after = parse("created_after", &self.created_after)?;
+        let before = parse("created_before", &self.created_before)?;
+        let completed = self.completed;
+        Ok(move |task: &TaskInfo| {
+            if completed.is_some_and(|c| c != task.completed) {
+                return false;
+            }
+            if after.is_none() && before.is_none() {
+                return true;
+            }
+            // Tasks without a readable created_at can't be placed in the range
+            chrono::DateTime::parse_from_rfc3339(&task.created_at).is_ok_and(|created| {
+                after.is_none_or(|a| created > a) && before.is_none_or(|b| created < b)
+            })
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskWithStats {
+    #[serde(flatten)]
+    task: TaskInfo,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    stats: Option<TaskStats>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskStats {
+    wins: u32,
+    losses: u32,
+    last_compared_at: Option<String>,
+    score: f64,
+    rank: usize,
 }
 
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ComparisonsResponse {
     comparisons: Vec<Comparison>,
+    // Latest timestamp among all comparisons, to pass as `since` next time
+    cursor: Option<String>,
+    // `comparisons` is the whole list rather than a delta: there was no
+    // `since`, or comparisons were removed after it. Replace the local copy.
+    full_resync: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ComparisonsQuery {
+    // Only comparisons made strictly after this RFC 3339 timestamp, oldest first
+    since: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DependenciesResponse {
+    dependencies: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct DuplicatesQuery {
+    #[serde(default)]
+    metric: SimilarityMetric,
+    // Between 0 and 1; lower finds more (and looser) duplicates
+    threshold: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DuplicatesResponse {
+    // Groups of tasks that look like the same todo, each ordered by id
+    clusters: Vec<Vec<TaskInfo>>,
+    threshold: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PruneResponse {
+    pruned: usize,
+    dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportOrderedRequest {
+    // Existing manual order, most important first
+    ordered_contents: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ResetQuery {
+    // Only remove the comparisons seeded by POST /import/ordered
+    #[serde(default)]
+    seeded_only: bool,
+}
+
+// The same result recorded more than once for one pair of tasks, whichever
+// of them was task a
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DuplicateGroup {
+    task_a_id: usize,
+    task_b_id: usize,
+    winner_id: usize,
+    task_a: String,
+    task_b: String,
+    winner: String,
+    count: usize,
+    oldest_timestamp: String,
+    newest_timestamp: String,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeduplicateResponse {
+    removed: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResetResponse {
+    removed: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RollbackRequest {
+    // RFC 3339; comparisons made at or after this go
+    before_timestamp: String,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RollbackResponse {
+    removed: usize,
+    dry_run: bool,
+}
+
+// Body of every 4xx/5xx JSON response
+#[cfg(feature = "openapi")]
+#[derive(utoipa::ToSchema)]
+#[allow(dead_code)]
+pub struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ASAPStats {
+    // Estimated total comparisons needed, from the ASAP paper's bound and a
+    // simple n log n sorting estimate
+    theoretical_comparisons_bound: usize,
+    heuristic_comparisons_bound: usize,
+    // How hard the solver worked, for tuning its threshold and iteration cap
+    solver_iterations: usize,
+    solver_ms: f64,
+    // Mean rank displacement per comparison over the last few comparisons;
+    // near 0 means the order has settled
+    rank_stability: f64,
+    // Average progress of the task variances towards the target variance, 0-100
+    percent_confident: f64,
+    // Contents of tasks that keep losing and sit in the bottom quarter of the
+    // ranking, which may not be worth keeping
+    candidates_for_removal: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct RankingsResponse {
     rankings: Vec<RankedTask>,
+    stats: ASAPStats,
+    // Task ids of the pair the model suggests comparing next
+    next_pair: Option<(usize, usize)>,
+    // (prerequisite_id, dependent_id) pairs the ranking was constrained by
+    dependencies: Vec<(usize, usize)>,
+    // Most informative pairs to compare, with include_heatmap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comparisons_heatmap: Option<Vec<PairInfo>>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairInfo {
+    task_a_id: usize,
+    task_b_id: usize,
+    task_a: String,
+    task_b: String,
+    direct_comparisons: usize,
+    expected_information_gain: f64,
+    // Model's probability that task a beats task b
+    win_probability: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlgorithmRankedTask {
+    #[serde(flatten)]
+    task: RankedTask,
+    // The models disagree on this task's rank by more than a couple places
+    controversial: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlgorithmRanking {
+    name: String,
+    ranked_tasks: Vec<AlgorithmRankedTask>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CompareAlgorithmsResponse {
+    algorithms: Vec<AlgorithmRanking>,
+    // Task id -> mean rank across all models
+    consensus_rank: HashMap<String, f64>,
 }
 
 pub async fn run_web_service() {
-    // Initialize tracing for better logging
-    tracing_subscriber::fmt::init();
+    // Initialize tracing for better logging. RUST_LOG picks the level (e.g.
+    // RUST_LOG=sorter::web_service=debug), defaulting to info
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+    
+    // Panics inside handlers are turned into 500s below; make sure they still
+    // show up in the logs, with the backtrace from where they happened
+    std::panic::set_hook(Box::new(|info| {
+        tracing::error!("{}\n{}", info, std::backtrace::Backtrace::force_capture());
+    }));
     
     // Create the application state
-    let app_state = Arc::new(AppState {
-        tasks: Mutex::new(HashMap::new()),
-        comparisons: Mutex::new(Vec::new()),
-    });
+    let mut builder = AppStateBuilder::default()
+        .ranking_config(RankingConfig::from_env())
+        .webhook(WebhookConfig::from_env())
+        .audit_log(audit::path_from_env());
+    if let Some(max_tasks) = std::env::var("MAX_TASKS_PER_LIST").ok().and_then(|s| s.parse().ok()) {
+        builder = builder.max_tasks(max_tasks);
+    }
+    let app_state = match builder.build() {
+        Ok(state) => state,
+        Err(e) => {
+            tracing::error!("Invalid server configuration: {}", e);
+            return;
+        }
+    };
     
     // Define CORS policy to allow requests from frontend
     let cors = CorsLayer::new()
@@ -93,13 +795,51 @@ pub async fn run_web_service() {
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // Requests that take longer than this (e.g. a solver stuck at its
+    // iteration cap) are aborted with a 503 instead of hanging the connection
+    let request_timeout = request_timeout_from_env();
+    tracing::info!("Request timeout set to {:?}", request_timeout);
+
     // Create our API router
-    let app = Router::new()
+    let routes = Router::new()
         .route("/health", get(health_check))
         .route("/tasks", get(get_tasks).post(add_task))
+        .route("/tasks/prune", post(prune_orphaned_tasks))
+        .route("/tasks/batch-lookup", post(batch_lookup_tasks))
+        .route("/tasks/duplicates", get(find_duplicate_tasks))
+        .route("/tasks/comparison-partners", post(get_comparison_partners))
+        .route("/tasks/set-prior-batch", post(set_prior_batch))
+        .route("/tags", get(get_tags))
+        .route("/tags/priors", post(set_tag_priors))
+        .route("/dependencies", get(get_dependencies).post(add_dependency).delete(remove_dependency))
         .route("/comparisons", get(get_comparisons).post(add_comparison))
+        .route("/comparisons/add-group", post(add_group_comparison))
+        .route("/comparisons/import-ratings-log", post(import_ratings_log))
+        .route("/comparisons/reset", post(reset_comparisons))
+        .route("/comparisons/rollback", post(rollback_comparisons))
+        .route("/comparisons/duplicates", get(find_duplicate_comparisons))
+        .route("/comparisons/deduplicate", post(deduplicate_comparisons))
+        .route("/import/ordered", post(import_ordered))
         .route("/rankings", get(get_rankings))
+        .route("/rankings/pdf-export", get(get_rankings_pdf))
+        .route("/rankings/svg", get(get_rankings_svg))
+        .route("/probabilities", get(get_probabilities))
+        .route("/pair", post(get_pair))
+        .route("/consistency", post(get_consistency))
+        .route("/rankings/compare-algorithms", get(get_compare_algorithms))
+        .route("/embed/rankings", get(get_embed_rankings));
+    #[cfg(feature = "openapi")]
+    let routes = routes.route("/openapi.json", get(openapi_json));
+    
+    let app = routes
         .with_state(app_state)
+        .layer(
+            ServiceBuilder::new()
+                .layer(CatchPanicLayer::custom(handle_panic))
+                .layer(HandleErrorLayer::new(handle_timeout_error))
+                .timeout(request_timeout),
+        )
+        .layer(middleware::from_fn(log_requests))
         .layer(cors);
 
     // Run our service
@@ -107,29 +847,252 @@ pub async fn run_web_service() {
     tracing::info!("Listening on {}", addr);
     
     let listener = TcpListener::bind(addr).await.unwrap();
+    // Connection info gives log_requests the client address
+    let app = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
     axum::serve(listener, app).await.unwrap();
 }
 
+#[cfg(feature = "openapi")]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    info(title = "todo-sorter"),
+    paths(
+        health_check,
+        get_tasks,
+        add_task,
+        prune_orphaned_tasks,
+        batch_lookup_tasks,
+        find_duplicate_tasks,
+        get_comparison_partners,
+        set_prior_batch,
+        get_tags,
+        set_tag_priors,
+        get_dependencies,
+        add_dependency,
+        remove_dependency,
+        get_comparisons,
+        add_comparison,
+        add_group_comparison,
+        import_ratings_log,
+        reset_comparisons,
+        rollback_comparisons,
+        find_duplicate_comparisons,
+        deduplicate_comparisons,
+        import_ordered,
+        get_rankings,
+        get_rankings_pdf,
+        get_rankings_svg,
+        get_probabilities,
+        get_pair,
+        get_consistency,
+        get_compare_algorithms,
+        get_embed_rankings,
+    ),
+    components(schemas(ErrorResponse, PairStrategy, SimilarityMetric, TagMatch))
+)]
+struct ApiDoc;
+
+// The OpenAPI description of every route above
+#[cfg(feature = "openapi")]
+async fn openapi_json() -> impl IntoResponse {
+    use utoipa::OpenApi;
+    Json(ApiDoc::openapi())
+}
+
+fn request_timeout_from_env() -> Duration {
+    let secs = std::env::var("REQUEST_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+// Log every request once it has been answered, including timeouts and
+// panics, which the layers inside this one turn into responses. The details
+// are tracing fields, so a structured subscriber can pick them up as such.
+async fn log_requests(request: Request, next: Next) -> axum::response::Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<std::net::SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string());
+    
+    let response = next.run(request).await;
+    
+    tracing::info!(
+        target: "sorter::requests",
+        method = %method,
+        path = %path,
+        user_agent = user_agent.as_deref(),
+        ip = ip.as_deref(),
+        response_status = response.status().as_u16(),
+        response_time_ms = start.elapsed().as_millis() as u64,
+        "request"
+    );
+    response
+}
+
+// Convert errors from the timeout layer into HTTP responses
+async fn handle_timeout_error(err: BoxError) -> impl IntoResponse {
+    if err.is::<tower::timeout::error::Elapsed>() {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
+            "error": "Request timed out"
+        })))
+    } else {
+        tracing::error!("Unhandled middleware error: {}", err);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "error": "Internal server error"
+        })))
+    }
+}
+
+// A handler panicked (e.g. the solver's NaN check); answer with a 500 rather
+// than dropping the connection. The panic hook has already logged it.
+fn handle_panic(err: Box<dyn std::any::Any + Send + 'static>) -> axum::response::Response {
+    let message = err
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| err.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+    tracing::error!("Request handler panicked: {}", message);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+        "error": "internal_error",
+        "message": "An unexpected error occurred"
+    }))).into_response()
+}
+
 // Health check endpoint
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/health",
+    responses((status = 200, description = "Service is up")),
+))]
 async fn health_check() -> impl IntoResponse {
     StatusCode::OK
 }
 
-// Get all tasks
-async fn get_tasks(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let tasks = state.tasks.lock().unwrap();
-    let tasks_vec: Vec<TaskInfo> = tasks.values().cloned().collect();
+// Get all tasks, with include_stats also their win / loss record and current
+// rank, saving a separate call to /rankings. completed, created_after and
+// created_before narrow the list; ranks stay those within the whole list.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/tasks",
+    params(TasksQuery),
+    responses(
+        (status = 200, body = TasksResponse),
+        (status = 400, description = "Invalid created_after or created_before", body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_tasks(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TasksQuery>,
+) -> impl IntoResponse {
+    let keep = match query.filter() {
+        Ok(keep) => keep,
+        Err(error) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error }))).into_response();
+        }
+    };
+    let tasks = state.tasks.lock().unwrap().clone();
+    if !query.include_stats {
+        let tasks = tasks
+            .into_values()
+            .filter(|task| keep(task))
+            .map(|task| TaskWithStats { task, stats: None })
+            .collect();
+        return Json(TasksResponse { tasks }).into_response();
+    }
+    
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let dependencies = state.dependencies.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    let result = run_solver(move || {
+        let rankings = if tasks.len() < 2 {
+            Vec::new()
+        } else {
+            compute_rankings(&tasks, &comparisons, config, PairStrategy::Eig, false, &dependencies, None).rankings
+        };
+        let ranked: HashMap<usize, (f64, usize)> =
+            rankings.iter().map(|t| (t.id, (t.score, t.rank))).collect();
+        
+        // id -> (wins, losses, last comparison)
+        let mut records: HashMap<usize, (u32, u32, Option<&str>)> = HashMap::new();
+        for comp in &comparisons {
+            for id in [comp.task_a_id, comp.task_b_id] {
+                let record = records.entry(id).or_default();
+                if comp.winner_id == id {
+                    record.0 += 1;
+                } else {
+                    record.1 += 1;
+                }
+                if record.2.is_none_or(|last| last < comp.timestamp.as_str()) {
+                    record.2 = Some(&comp.timestamp);
+                }
+            }
+        }
+        
+        let mut with_stats: Vec<TaskWithStats> = tasks
+            .values()
+            .filter(|task| keep(task))
+            .map(|task| {
+                let (wins, losses, last) = records.get(&task.id).copied().unwrap_or_default();
+                let (score, rank) = ranked.get(&task.id).copied().unwrap_or((0.0, 1));
+                TaskWithStats {
+                    task: task.clone(),
+                    stats: Some(TaskStats {
+                        wins,
+                        losses,
+                        last_compared_at: last.map(String::from),
+                        score,
+                        rank,
+                    }),
+                }
+            })
+            .collect();
+        with_stats.sort_by_key(|t| t.stats.as_ref().map(|s| s.rank));
+        with_stats
+    })
+    .await;
     
-    Json(TasksResponse { tasks: tasks_vec })
+    match result {
+        Ok(tasks) => Json(TasksResponse { tasks }).into_response(),
+        Err(response) => response,
+    }
 }
 
 // Add a new task
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/tasks",
+    request_body = AddTaskRequest,
+    responses(
+        (status = 201, body = AddTaskResponse),
+        (status = 400, description = "Task limit reached or invalid score", body = ErrorResponse),
+    ),
+))]
 async fn add_task(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<AddTaskRequest>,
 ) -> impl IntoResponse {
     let mut tasks = state.tasks.lock().unwrap();
     
+    if tasks.len() >= state.max_tasks {
+        return too_many_tasks(state.max_tasks);
+    }
+    
+    if payload.anchor_score.is_some_and(|s| !valid_score(s)) {
+        return invalid_score("anchor_score");
+    }
+    if payload.initial_score.is_some_and(|s| !valid_score(s)) {
+        return invalid_score("initial_score");
+    }
+    
     // Generate a new ID for the task
     let new_id = if let Some(max_id) = tasks.keys().max() {
         max_id + 1
@@ -140,75 +1103,632 @@ async fn add_task(
     // Create the new task
     let new_task = TaskInfo {
         id: new_id,
-        content: payload.content,
+        content: payload.content.clone(),
         completed: payload.completed,
         line: payload.line,
         file: payload.file,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        anchor_score: payload.anchor_score,
+        prior_win_rate: None,
+        initial_score: payload.initial_score,
+        tag_prior: tag_prior(&hashtags(&payload.content), &state.tag_priors.lock().unwrap()),
+        tags: hashtags(&payload.content),
     };
     
     tasks.insert(new_id, new_task.clone());
     
-    (StatusCode::CREATED, Json(new_task))
+    let initial_score_applied = new_task.initial_score.is_some();
+    (StatusCode::CREATED, Json(AddTaskResponse { task: new_task, initial_score_applied })).into_response()
+}
+
+fn too_many_tasks(max_tasks: usize) -> axum::response::Response {
+    (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+        "error": format!("Too many tasks, at most {} are allowed", max_tasks)
+    }))).into_response()
+}
+
+// Whether a hand-set score (anchor, initial score, tag prior) is usable
+fn valid_score(score: f64) -> bool {
+    score.is_finite() && score.abs() <= MAX_ABS_SCORE
+}
+
+fn invalid_score(name: &str) -> axum::response::Response {
+    (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+        "error": format!("{} must be a number between -{} and {}", name, MAX_ABS_SCORE, MAX_ABS_SCORE)
+    }))).into_response()
 }
 
-// Get all comparisons
-async fn get_comparisons(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+// Delete tasks that were never compared and are older than the given age.
+// With dry_run, only report how many would go.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/tasks/prune",
+    request_body = PruneRequest,
+    responses(
+        (status = 200, body = PruneResponse),
+        (status = 400, description = "older_than_days is out of range", body = ErrorResponse),
+    ),
+))]
+async fn prune_orphaned_tasks(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<PruneRequest>,
+) -> impl IntoResponse {
+    // Before taking any lock: a panic while holding them would poison both
+    let Some(cutoff) = prune_cutoff(chrono::Utc::now(), payload.older_than_days) else {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "older_than_days is out of range"
+        }))).into_response();
+    };
+    
+    let mut tasks = state.tasks.lock().unwrap();
     let comparisons = state.comparisons.lock().unwrap();
     
-    Json(ComparisonsResponse {
-        comparisons: comparisons.clone(),
+    let compared: HashSet<usize> = comparisons
+        .iter()
+        .flat_map(|c| [c.task_a_id, c.task_b_id])
+        .collect();
+    
+    let orphaned: Vec<usize> = tasks
+        .values()
+        .filter(|t| !compared.contains(&t.id))
+        .filter(|t| {
+            chrono::DateTime::parse_from_rfc3339(&t.created_at)
+                .is_ok_and(|created| created < cutoff)
+        })
+        .map(|t| t.id)
+        .collect();
+    
+    if !payload.dry_run {
+        for id in &orphaned {
+            tasks.remove(id);
+        }
+        state
+            .dependencies
+            .lock()
+            .unwrap()
+            .retain(|(pre, dep)| tasks.contains_key(pre) && tasks.contains_key(dep));
+        tracing::info!("Pruned {} orphaned tasks", orphaned.len());
+    }
+    
+    Json(PruneResponse {
+        pruned: orphaned.len(),
+        dry_run: payload.dry_run,
     })
+    .into_response()
 }
 
-// Add a new comparison
-async fn add_comparison(
+// `older_than_days` before `now`, or None if that's before the earliest
+// representable date
+fn prune_cutoff(now: chrono::DateTime<chrono::Utc>, older_than_days: u32) -> Option<chrono::DateTime<chrono::Utc>> {
+    now.checked_sub_signed(chrono::TimeDelta::try_days(older_than_days.into())?)
+}
+
+// Find clusters of tasks whose texts are similar enough to be the same todo
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/tasks/duplicates",
+    params(DuplicatesQuery),
+    responses(
+        (status = 200, body = DuplicatesResponse),
+        (status = 400, body = ErrorResponse),
+    ),
+))]
+async fn find_duplicate_tasks(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<AddComparisonRequest>,
+    Query(query): Query<DuplicatesQuery>,
+) -> impl IntoResponse {
+    let threshold = query.threshold.unwrap_or(DEFAULT_DUPLICATE_THRESHOLD);
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "threshold must be greater than 0 and at most 1"
+        }))).into_response();
+    }
+    
+    let tasks = state.tasks.lock().unwrap().clone();
+    let found = run_solver(move || {
+        let items: Vec<(usize, &str)> = tasks.values().map(|t| (t.id, t.content.as_str())).collect();
+        duplicate_clusters(&items, query.metric, threshold)
+            .into_iter()
+            .map(|cluster| cluster.iter().map(|id| tasks[id].clone()).collect())
+            .collect()
+    })
+    .await;
+    
+    match found {
+        Ok(clusters) => Json(DuplicatesResponse { clusters, threshold }).into_response(),
+        Err(response) => response,
+    }
+}
+
+// Get all task dependencies as (prerequisite_id, dependent_id) pairs
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/dependencies",
+    responses((status = 200, body = DependenciesResponse)),
+))]
+async fn get_dependencies(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let dependencies = state.dependencies.lock().unwrap();
+    
+    Json(DependenciesResponse {
+        dependencies: dependencies.clone(),
+    })
+}
+
+// Record that one task must be done before another
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/dependencies",
+    request_body = DependencyRequest,
+    responses(
+        (status = 201, body = DependencyRequest),
+        (status = 400, body = ErrorResponse),
+        (status = 409, description = "The dependency would close a cycle", body = ErrorResponse),
+    ),
+))]
+async fn add_dependency(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<DependencyRequest>,
 ) -> impl IntoResponse {
     let tasks = state.tasks.lock().unwrap();
     
-    // Validate that the task IDs exist
-    if !tasks.contains_key(&payload.task_a_id) || 
-       !tasks.contains_key(&payload.task_b_id) || 
-       !tasks.contains_key(&payload.winner_id) {
+    if !tasks.contains_key(&payload.prerequisite_id) || !tasks.contains_key(&payload.dependent_id) {
         return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
             "error": "Invalid task ID provided"
         }))).into_response();
     }
     
-    // Validate that the winner ID is one of the tasks being compared
-    if payload.winner_id != payload.task_a_id && payload.winner_id != payload.task_b_id {
+    if payload.prerequisite_id == payload.dependent_id {
         return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-            "error": "Winner ID must be either task_a_id or task_b_id"
+            "error": "A task can't depend on itself"
         }))).into_response();
     }
     
-    // Create the new comparison
-    let new_comparison = Comparison {
-        task_a_id: payload.task_a_id,
+    let dependency = (payload.prerequisite_id, payload.dependent_id);
+    let mut dependencies = state.dependencies.lock().unwrap();
+    // In a cycle every task would have to rank above an unfinished
+    // prerequisite, so refuse the edge that would close one
+    if depends_on(&dependencies, payload.prerequisite_id, payload.dependent_id) {
+        return (StatusCode::CONFLICT, Json(serde_json::json!({
+            "error": format!(
+                "Task {} already depends on task {}, directly or indirectly",
+                payload.prerequisite_id, payload.dependent_id
+            )
+        }))).into_response();
+    }
+    if !dependencies.contains(&dependency) {
+        dependencies.push(dependency);
+    }
+    
+    (StatusCode::CREATED, Json(payload)).into_response()
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete, path = "/dependencies",
+    request_body = DependencyRequest,
+    responses(
+        (status = 204, description = "Dependency removed"),
+        (status = 404, description = "No such dependency"),
+    ),
+))]
+async fn remove_dependency(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<DependencyRequest>,
+) -> impl IntoResponse {
+    let mut dependencies = state.dependencies.lock().unwrap();
+    let before = dependencies.len();
+    dependencies.retain(|&d| d != (payload.prerequisite_id, payload.dependent_id));
+    
+    if dependencies.len() == before {
+        return StatusCode::NOT_FOUND;
+    }
+    StatusCode::NO_CONTENT
+}
+
+// Get all comparisons, or with `since` only the ones newer than a previous
+// response's cursor, so syncing clients don't download the whole list again.
+// Removals can't be expressed as a delta, so after one the full list is sent.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/comparisons",
+    params(ComparisonsQuery),
+    responses(
+        (status = 200, body = ComparisonsResponse),
+        (status = 400, description = "Invalid since", body = ErrorResponse),
+    ),
+))]
+async fn get_comparisons(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ComparisonsQuery>,
+) -> impl IntoResponse {
+    let since = match query.since.as_deref().map(chrono::DateTime::parse_from_rfc3339).transpose() {
+        Ok(since) => since,
+        Err(_) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "since must be an RFC 3339 timestamp"
+            }))).into_response();
+        }
+    };
+    let comparisons = state.comparisons.lock().unwrap();
+    
+    // Timestamps are compared as instants, not strings, since offsets differ
+    let timed = comparisons
+        .iter()
+        .filter_map(|c| chrono::DateTime::parse_from_rfc3339(&c.timestamp).ok().map(|t| (t, c)));
+    let cursor = timed.clone().max_by_key(|(t, _)| *t).map(|(_, c)| c.timestamp.clone());
+    let removed_at = *state.comparisons_removed_at.lock().unwrap();
+    let (comparisons, full_resync) = match since {
+        Some(since) if removed_at < since => {
+            let mut newer: Vec<_> = timed.filter(|(t, _)| *t > since).collect();
+            newer.sort_by_key(|(t, _)| *t);
+            (newer.into_iter().map(|(_, c)| c.clone()).collect(), false)
+        }
+        _ => (comparisons.clone(), true),
+    };
+    
+    Json(ComparisonsResponse { comparisons, cursor, full_resync }).into_response()
+}
+
+// Add a new comparison
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/comparisons",
+    request_body = AddComparisonRequest,
+    responses(
+        (status = 201, body = Comparison),
+        (status = 400, body = ErrorResponse),
+    ),
+))]
+async fn add_comparison(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AddComparisonRequest>,
+) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap();
+    
+    let winner_id = match (payload.winner_id, payload.winner) {
+        (Some(id), None) => id,
+        (None, Some(Side::A)) => payload.task_a_id,
+        (None, Some(Side::B)) => payload.task_b_id,
+        _ => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Exactly one of winner_id and winner must be given"
+            }))).into_response();
+        }
+    };
+    
+    // Validate that the task IDs exist
+    if !tasks.contains_key(&payload.task_a_id) || 
+       !tasks.contains_key(&payload.task_b_id) || 
+       !tasks.contains_key(&winner_id) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "Invalid task ID provided"
+        }))).into_response();
+    }
+    
+    // Validate that the winner ID is one of the tasks being compared
+    if winner_id != payload.task_a_id && winner_id != payload.task_b_id {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "Winner ID must be either task_a_id or task_b_id"
+        }))).into_response();
+    }
+    
+    if payload.draw_margin.is_some_and(|m| !(0.0..=MAX_DRAW_MARGIN).contains(&m)) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("draw_margin must be a number between 0 and {}", MAX_DRAW_MARGIN)
+        }))).into_response();
+    }
+    
+    // Create the new comparison
+    let new_comparison = Comparison {
+        task_a_id: payload.task_a_id,
         task_b_id: payload.task_b_id,
-        winner_id: payload.winner_id,
+        winner_id,
         timestamp: chrono::Utc::now().to_rfc3339(),
+        draw_margin: payload.draw_margin,
+        seeded: false,
     };
     
     // Add the comparison to our list
-    let mut comparisons = state.comparisons.lock().unwrap();
-    comparisons.push(new_comparison.clone());
+    let earlier = {
+        let mut comparisons = state.comparisons.lock().unwrap();
+        let earlier = state.audit_log.is_some().then(|| comparisons.clone());
+        comparisons.push(new_comparison.clone());
+        earlier
+    };
+    
+    if let Some(earlier) = earlier {
+        tokio::spawn(audit_comparisons(state.clone(), earlier, vec![new_comparison.clone()]));
+    }
+    if state.webhook.is_some() {
+        tokio::spawn(notify_if_converged(state.clone()));
+    }
     
     (StatusCode::CREATED, Json(new_comparison)).into_response()
 }
 
-// Get rankings using the ASAP algorithm
-async fn get_rankings(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let tasks = state.tasks.lock().unwrap();
-    let comparisons = state.comparisons.lock().unwrap();
+// Log how each of `added` moved the scores, solving after `earlier` and then
+// after each added comparison in turn. Runs in the background after a
+// comparison.
+async fn audit_comparisons(state: Arc<AppState>, earlier: Vec<Comparison>, added: Vec<Comparison>) {
+    let Some(path) = state.audit_log.clone() else { return };
+    let tasks = state.tasks.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    let result = run_solver(move || -> std::io::Result<()> {
+        let all: Vec<Comparison> = earlier.iter().chain(&added).cloned().collect();
+        let (id_to_index, _) = comparison_matrix(&tasks, &all);
+        let mut items = vec![None; id_to_index.len()];
+        for (id, &i) in &id_to_index {
+            items[i] = Some((*id, tasks[id].content.as_str()));
+        }
+        
+        let mut asap = new_asap(&tasks, &all, &id_to_index, config);
+        let (_, mut before, _) = asap.run_solver_on(&chronological_cmps(&earlier, &id_to_index));
+        for comp in &added {
+            let (Some(&i), Some(&j)) = (id_to_index.get(&comp.task_a_id), id_to_index.get(&comp.task_b_id)) else {
+                continue;
+            };
+            let (_, after, _) = asap.run_solver_on(&chronological_cmps(std::slice::from_ref(comp), &id_to_index));
+            let changes = audit::score_changes(&items, &before, &after, [i, j]);
+            audit::append(&path, [comp.task_a_id, comp.task_b_id], comp.winner_id, &changes)?;
+            before = after;
+        }
+        Ok(())
+    })
+    .await;
+    
+    if let Ok(Err(e)) = result {
+        tracing::warn!("Failed to write the audit log: {}", e);
+    }
+}
+
+// Send the convergence webhooks if the ranking has settled, at most once per
+// CONVERGENCE_NOTIFY_COOLDOWN. Runs in the background after a comparison.
+async fn notify_if_converged(state: Arc<AppState>) {
+    let Some(webhook) = &state.webhook else { return };
+    let cooling_down = |last: &Option<Instant>| last.is_some_and(|t| t.elapsed() < CONVERGENCE_NOTIFY_COOLDOWN);
+    if cooling_down(&state.last_notified_at.lock().unwrap()) {
+        return;
+    }
+    
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let dependencies = state.dependencies.lock().unwrap().clone();
+    let config = state.ranking_config;
+    // With fewer comparisons than the stability window the score says little
+    if comparisons.len() < RANK_STABILITY_WINDOW {
+        return;
+    }
+    let Ok((score, top)) = run_solver(move || {
+        let (id_to_index, _) = comparison_matrix(&tasks, &comparisons);
+        let mut asap = new_asap(&tasks, &comparisons, &id_to_index, config);
+        let (_, ms, _) = asap.run_solver_on(&chronological_cmps(&comparisons, &id_to_index));
+        let rankings = rank_by_score(&tasks, &comparisons, &id_to_index, &ms, false);
+        let top: Vec<(String, f64)> = respect_dependencies(rankings, &dependencies)
+            .into_iter()
+            .take(CONVERGENCE_NOTIFY_TOP)
+            .map(|t| (t.content, t.score))
+            .collect();
+        (asap.rank_stability_score(RANK_STABILITY_WINDOW), top)
+    })
+    .await
+    else {
+        return;
+    };
+    
+    if score >= config.convergence_threshold {
+        return;
+    }
+    {
+        let mut last = state.last_notified_at.lock().unwrap();
+        if cooling_down(&last) {
+            return;
+        }
+        *last = Some(Instant::now());
+    }
+    notify_converged(webhook, score, &top).await;
+}
+
+// Record a full ordering of several tasks (e.g. a race) as the pairwise
+// comparisons it implies: every task beats all the ones placed after it
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/comparisons/add-group",
+    request_body = AddGroupComparisonRequest,
+    responses(
+        (status = 201, body = Vec<Comparison>),
+        (status = 400, body = ErrorResponse),
+    ),
+))]
+async fn add_group_comparison(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AddGroupComparisonRequest>,
+) -> impl IntoResponse {
+    let ordered = payload.ordered_task_ids;
+    if !(2..=MAX_GROUP_SIZE).contains(&ordered.len()) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("A group ranking needs between 2 and {} tasks", MAX_GROUP_SIZE)
+        }))).into_response();
+    }
+    if ordered.iter().collect::<HashSet<_>>().len() != ordered.len() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "Each task can only appear once in a group ranking"
+        }))).into_response();
+    }
+    {
+        let tasks = state.tasks.lock().unwrap();
+        if !ordered.iter().all(|id| tasks.contains_key(id)) {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Invalid task ID provided"
+            }))).into_response();
+        }
+    }
+    
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let new_comparisons: Vec<Comparison> = group_comparisons(&ordered)
+        .into_iter()
+        .map(|[winner, loser]| Comparison {
+            task_a_id: winner,
+            task_b_id: loser,
+            winner_id: winner,
+            timestamp: timestamp.clone(),
+            draw_margin: None,
+            seeded: false,
+        })
+        .collect();
+    let earlier = {
+        let mut comparisons = state.comparisons.lock().unwrap();
+        let earlier = state.audit_log.is_some().then(|| comparisons.clone());
+        comparisons.extend(new_comparisons.iter().cloned());
+        earlier
+    };
+    
+    if let Some(earlier) = earlier {
+        tokio::spawn(audit_comparisons(state.clone(), earlier, new_comparisons.clone()));
+    }
+    if state.webhook.is_some() {
+        tokio::spawn(notify_if_converged(state.clone()));
+    }
+    
+    (StatusCode::CREATED, Json(new_comparisons)).into_response()
+}
+
+// Rank tasks by running the ASAP algorithm over the recorded comparisons.
+// This is CPU-bound, so callers in async handlers should use spawn_blocking.
+fn compute_rankings(
+    tasks: &HashMap<usize, TaskInfo>,
+    comparisons: &[Comparison],
+    config: RankingConfig,
+    strategy: PairStrategy,
+    newest_first: bool,
+    dependencies: &[(usize, usize)],
+    heatmap_k: Option<usize>,
+) -> RankingsResponse {
+    let n = tasks.len();
+    let (id_to_index, m) = comparison_matrix(tasks, comparisons);
+    
+    // Run the ASAP algorithm to get ratings
+    let mut asap = new_asap(tasks, comparisons, &id_to_index, config);
+    let (prob, ms_curr, vs_curr) = asap.run_solver_on(&chronological_cmps(comparisons, &id_to_index));
+    
+    // Pick the next pair. Insertion sort falls back to EIG once every task has
+    // been placed. The full information gain search runs the solver once per
+    // pair, so large lists use the cheap heuristic instead.
+    let insertion = match strategy {
+        PairStrategy::Insertion => {
+            // Task ids are handed out incrementally, so they follow creation order
+            let mut ids: Vec<usize> = tasks.keys().copied().collect();
+            ids.sort_unstable();
+            let order: Vec<usize> = ids.iter().map(|id| id_to_index[id]).collect();
+            insertion_pair(&order, &ms_curr, &m)
+        }
+        PairStrategy::Eig => None,
+    };
+    let pair = insertion.or_else(|| {
+        if n > config.eig_max_tasks {
+            let window = (n > WINDOWED_PAIR_SEARCH_MIN_TASKS).then_some(PAIR_SEARCH_WINDOW);
+            asap.most_informative_uncompared_pairs(1, window).first().copied()
+        } else {
+            Some(asap.optimal_pair(&prob, &ms_curr, &vs_curr))
+        }
+    });
+    let index_to_id: HashMap<usize, usize> = id_to_index.iter().map(|(&k, &v)| (v, k)).collect();
+    let next_pair = pair.and_then(|(i, j)| Some((*index_to_id.get(&i)?, *index_to_id.get(&j)?)));
+    
+    let rankings = rank_by_score(tasks, comparisons, &id_to_index, &ms_curr, newest_first);
+    let rankings = respect_dependencies(rankings, dependencies);
+    
+    let stats = ASAPStats {
+        theoretical_comparisons_bound: asap.theoretical_comparisons_bound(DEFAULT_TARGET_VARIANCE),
+        heuristic_comparisons_bound: asap.heuristic_comparisons_bound(),
+        solver_iterations: asap.solver_iterations(),
+        solver_ms: asap.solve_time().as_secs_f64() * 1000.0,
+        rank_stability: asap.rank_stability_score(RANK_STABILITY_WINDOW),
+        percent_confident: asap.percent_confident(DEFAULT_TARGET_VARIANCE),
+        candidates_for_removal: removal_candidates(&rankings, comparisons),
+    };
     
-    // If we don't have enough tasks or comparisons, return an empty response
-    if tasks.len() < 2 || comparisons.is_empty() {
-        return Json(RankingsResponse { rankings: Vec::new() }).into_response();
+    let comparisons_heatmap = heatmap_k.map(|k| {
+        asap.missing_comparisons_heatmap(k)
+            .into_iter()
+            .filter_map(|pair| {
+                let (a, b) = (*index_to_id.get(&pair.a)?, *index_to_id.get(&pair.b)?);
+                Some(PairInfo {
+                    task_a_id: a,
+                    task_b_id: b,
+                    task_a: tasks[&a].content.clone(),
+                    task_b: tasks[&b].content.clone(),
+                    direct_comparisons: pair.direct_comparisons,
+                    expected_information_gain: pair.expected_information_gain,
+                    win_probability: pair.win_probability,
+                })
+            })
+            .collect()
+    });
+    
+    RankingsResponse { rankings, stats, next_pair, dependencies: dependencies.to_vec(), comparisons_heatmap }
+}
+
+// Whether `task` has `prerequisite` among its prerequisites, following
+// (prerequisite, dependent) edges transitively
+fn depends_on(dependencies: &[(usize, usize)], task: usize, prerequisite: usize) -> bool {
+    let mut stack = vec![prerequisite];
+    let mut seen = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if id == task {
+            return true;
+        }
+        if seen.insert(id) {
+            stack.extend(dependencies.iter().filter(|(pre, _)| *pre == id).map(|(_, dep)| *dep));
+        }
     }
+    false
+}
+
+// Reorder rankings so no task sits above an unfinished prerequisite. This is a
+// topological sort that always places the best-ranked available task next, so
+// tasks not involved in any dependency keep their relative order. POST
+// /dependencies refuses cycles; one that got in anyway is broken by placing
+// its best-ranked task first.
+fn respect_dependencies(rankings: Vec<RankedTask>, dependencies: &[(usize, usize)]) -> Vec<RankedTask> {
+    let position: HashMap<usize, usize> = rankings.iter().enumerate().map(|(pos, t)| (t.id, pos)).collect();
+    let n = rankings.len();
     
-    // Convert our tasks and comparisons into the format expected by ASAP
+    let mut dependents = vec![Vec::new(); n];
+    let mut blocked_by = vec![0usize; n];
+    for (prerequisite, dependent) in dependencies {
+        if let (Some(&pre), Some(&dep)) = (position.get(prerequisite), position.get(dependent)) {
+            if !rankings[pre].completed {
+                dependents[pre].push(dep);
+                blocked_by[dep] += 1;
+            }
+        }
+    }
+    
+    let mut placed = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    while order.len() < n {
+        let next = (0..n)
+            .find(|&pos| !placed[pos] && blocked_by[pos] == 0)
+            .or_else(|| (0..n).find(|&pos| !placed[pos]))
+            .unwrap();
+        placed[next] = true;
+        order.push(next);
+        for &dep in &dependents[next] {
+            blocked_by[dep] = blocked_by[dep].saturating_sub(1);
+        }
+    }
+    
+    let mut slots: Vec<Option<RankedTask>> = rankings.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .enumerate()
+        .filter_map(|(rank, pos)| {
+            let mut task = slots[pos].take()?;
+            task.rank = rank + 1;
+            Some(task)
+        })
+        .collect()
+}
+
+// Convert our tasks and comparisons into the win matrix expected by ASAP,
+// along with the task id -> matrix index mapping
+fn comparison_matrix(
+    tasks: &HashMap<usize, TaskInfo>,
+    comparisons: &[Comparison],
+) -> (HashMap<usize, usize>, Vec<Vec<i32>>) {
     let n = tasks.len();
     let id_to_index: HashMap<usize, usize> = tasks
         .keys()
@@ -225,22 +1745,161 @@ async fn get_rankings(State(state): State<Arc<AppState>>) -> impl IntoResponse {
             m[i][j] += 1;
         }
     }
+    (id_to_index, m)
+}
+
+// [winner, loser] solver indices of every comparison, oldest first
+fn chronological_cmps(comparisons: &[Comparison], id_to_index: &HashMap<usize, usize>) -> Vec<[usize; 2]> {
+    comparisons
+        .iter()
+        .filter_map(|comp| {
+            let loser_id = if comp.winner_id == comp.task_a_id { comp.task_b_id } else { comp.task_a_id };
+            Some([*id_to_index.get(&comp.winner_id)?, *id_to_index.get(&loser_id)?])
+        })
+        .collect()
+}
+
+// Solver for the tasks in `id_to_index`, with per-comparison draw margins
+// applied. A task starts from its initial_score, else its prior_win_rate,
+// else its tag prior.
+fn new_asap(
+    tasks: &HashMap<usize, TaskInfo>,
+    comparisons: &[Comparison],
+    id_to_index: &HashMap<usize, usize>,
+    config: RankingConfig,
+) -> ASAP {
+    let mut asap = ASAP::with_config(id_to_index.len(), config.trueskill);
+    let mut win_rates = HashMap::new();
+    for (id, task) in tasks {
+        let Some(&i) = id_to_index.get(id) else { continue };
+        if let Some(score) = task.anchor_score {
+            asap.pin_anchor(i, score);
+        }
+        if let Some(rate) = task.prior_win_rate {
+            win_rates.insert(i, rate);
+        }
+        if let Some(mean) = task.tag_prior {
+            asap.set_prior(i, mean);
+        }
+    }
+    asap.add_prior_from_win_rates(&win_rates);
+    for (id, task) in tasks {
+        if let (Some(score), Some(&i)) = (task.initial_score, id_to_index.get(id)) {
+            asap.set_prior(i, score);
+        }
+    }
+    // Comparison ids follow chronological_cmps, which skips unknown tasks
+    let known = comparisons
+        .iter()
+        .filter(|c| id_to_index.contains_key(&c.task_a_id) && id_to_index.contains_key(&c.task_b_id));
+    for (comparison_id, comp) in known.enumerate() {
+        if let Some(margin) = comp.draw_margin {
+            asap.set_draw_margin(comparison_id, margin);
+        }
+    }
+    asap
+}
+
+// Lowercased #hashtags in `content`, each once, in order of appearance. A tag
+// starts with a letter, so "#1 priority" isn't one.
+fn hashtags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in content.split_whitespace() {
+        let Some(tag) = word.strip_prefix('#') else { continue };
+        let tag: String = tag
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+            .flat_map(char::to_lowercase)
+            .collect();
+        if tag.starts_with(char::is_alphabetic) && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+// The tags named in a `tags` query parameter, lowercased and without the #.
+// None when it names none (missing, or e.g. `tags=` / `tags=,`), i.e. no filter.
+fn tag_filter(filter: Option<&str>) -> Option<Vec<String>> {
+    let wanted: Vec<String> = filter?
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches('#').to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    (!wanted.is_empty()).then_some(wanted)
+}
+
+// Mean prior of the tags that have one
+fn tag_prior(tags: &[String], tag_priors: &HashMap<String, f64>) -> Option<f64> {
+    let priors: Vec<f64> = tags.iter().filter_map(|tag| tag_priors.get(tag).copied()).collect();
+    (!priors.is_empty()).then(|| priors.iter().sum::<f64>() / priors.len() as f64)
+}
+
+// Tasks in the bottom quarter of the ranking that have been compared at
+// least REMOVAL_MIN_COMPARISONS times and (almost) never won, worst first
+fn removal_candidates(rankings: &[RankedTask], comparisons: &[Comparison]) -> Vec<String> {
+    let mut record: HashMap<usize, (usize, usize)> = HashMap::new();
+    for comp in comparisons.iter() {
+        for id in [comp.task_a_id, comp.task_b_id] {
+            let (wins, games) = record.entry(id).or_default();
+            *games += 1;
+            if comp.winner_id == id {
+                *wins += 1;
+            }
+        }
+    }
     
-    // Run the ASAP algorithm to get ratings
-    let mut asap = ASAP::new(n);
-    let (_, _, ms_curr, _) = asap.run_asap(&m);
+    let bottom_quarter = rankings.len() - rankings.len() / 4;
+    rankings
+        .iter()
+        .rev()
+        .filter(|t| t.rank > bottom_quarter)
+        .filter(|t| {
+            let (wins, games) = record.get(&t.id).copied().unwrap_or_default();
+            games >= REMOVAL_MIN_COMPARISONS && wins as f64 <= REMOVAL_MAX_WIN_RATE * games as f64
+        })
+        .map(|t| t.content.clone())
+        .collect()
+}
+
+// Sort tasks by their score (indexed like the comparison matrix), highest
+// first. Ties, e.g. never-compared tasks all sitting at the prior, are broken
+// by creation time (oldest first unless `newest_first`) and then task id, so
+// the order is stable between requests.
+fn rank_by_score(
+    tasks: &HashMap<usize, TaskInfo>,
+    comparisons: &[Comparison],
+    id_to_index: &HashMap<usize, usize>,
+    scores: &[f64],
+    newest_first: bool,
+) -> Vec<RankedTask> {
+    let n = tasks.len();
+    
+    // Distinct comparison partners per task, for coverage
+    let mut partners: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for comp in comparisons.iter() {
+        if comp.task_a_id != comp.task_b_id {
+            partners.entry(comp.task_a_id).or_default().insert(comp.task_b_id);
+            partners.entry(comp.task_b_id).or_default().insert(comp.task_a_id);
+        }
+    }
     
-    // Create the rankings response
-    let mut rankings = Vec::new();
     let mut scores: Vec<(usize, f64)> = id_to_index
         .iter()
-        .map(|(&id, &index)| (id, ms_curr[index]))
+        .map(|(&id, &index)| (id, scores[index]))
         .collect();
+    let created = |id: &usize| {
+        let created_at = tasks.get(id).map(|t| t.created_at.as_str()).unwrap_or_default();
+        (chrono::DateTime::parse_from_rfc3339(created_at).ok(), *id)
+    };
+    scores.sort_by(|a, b| {
+        let age = created(&a.0).cmp(&created(&b.0));
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(if newest_first { age.reverse() } else { age })
+    });
     
-    // Sort by score (highest first)
-    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    
-    // Build the final rankings
+    let mut rankings = Vec::new();
     for (rank, (id, score)) in scores.iter().enumerate() {
         if let Some(task) = tasks.get(id) {
             rankings.push(RankedTask {
@@ -251,9 +1910,1105 @@ async fn get_rankings(State(state): State<Arc<AppState>>) -> impl IntoResponse {
                 file: task.file.clone(),
                 score: *score,
                 rank: rank + 1,
+                direct_comparisons: partners.get(id).map_or(0, |p| p.len()),
+                possible_direct: n - 1,
             });
         }
     }
+    rankings
+}
+
+// Rank the same comparisons with every model we have. The consensus rank is
+// the mean rank across models (Borda style); tasks whose rank spreads by more
+// than CONTROVERSIAL_RANK_SPREAD positions are flagged as controversial.
+fn compare_algorithms(
+    tasks: &HashMap<usize, TaskInfo>,
+    comparisons: &[Comparison],
+    config: RankingConfig,
+) -> CompareAlgorithmsResponse {
+    let (id_to_index, m) = comparison_matrix(tasks, comparisons);
+    
+    let mut asap = new_asap(tasks, comparisons, &id_to_index, config);
+    let (_, ms_curr, _) = asap.run_solver_on(&chronological_cmps(comparisons, &id_to_index));
+    
+    let models = [
+        ("trueskill", ms_curr),
+        ("bradley_terry", baselines::bradley_terry(&m)),
+        ("win_rate", baselines::win_rate(&m)),
+    ];
+    let rankings: Vec<(&str, Vec<RankedTask>)> = models
+        .iter()
+        .map(|(name, scores)| (*name, rank_by_score(tasks, comparisons, &id_to_index, scores, false)))
+        .collect();
+    
+    // (sum, min, max) of each task's rank across models
+    let mut rank_range: HashMap<usize, (usize, usize, usize)> = HashMap::new();
+    for (_, ranked) in rankings.iter() {
+        for task in ranked {
+            let entry = rank_range.entry(task.id).or_insert((0, usize::MAX, 0));
+            entry.0 += task.rank;
+            entry.1 = entry.1.min(task.rank);
+            entry.2 = entry.2.max(task.rank);
+        }
+    }
+    
+    let consensus_rank = rank_range
+        .iter()
+        .map(|(id, &(sum, _, _))| (id.to_string(), sum as f64 / rankings.len() as f64))
+        .collect();
+    let algorithms = rankings
+        .into_iter()
+        .map(|(name, ranked)| AlgorithmRanking {
+            name: name.to_string(),
+            ranked_tasks: ranked
+                .into_iter()
+                .map(|task| {
+                    let (_, min, max) = rank_range[&task.id];
+                    AlgorithmRankedTask {
+                        controversial: max - min > CONTROVERSIAL_RANK_SPREAD,
+                        task,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+    
+    CompareAlgorithmsResponse { algorithms, consensus_rank }
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/rankings/compare-algorithms",
+    responses(
+        (status = 200, body = CompareAlgorithmsResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_compare_algorithms(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    match run_solver(move || compare_algorithms(&tasks, &comparisons, config)).await {
+        Ok(response) => Json(response).into_response(),
+        Err(response) => response,
+    }
+}
+
+// Clear all comparisons but keep the tasks, to re-sort from scratch. With
+// seeded_only, just drop the ones POST /import/ordered made up.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/comparisons/reset",
+    params(ResetQuery),
+    responses((status = 200, body = ResetResponse)),
+))]
+async fn reset_comparisons(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ResetQuery>,
+) -> impl IntoResponse {
+    let mut comparisons = state.comparisons.lock().unwrap();
+    let before = comparisons.len();
+    if query.seeded_only {
+        comparisons.retain(|c| !c.seeded);
+    } else {
+        comparisons.clear();
+    }
+    let removed = before - comparisons.len();
+    if removed > 0 {
+        state.comparisons_removed();
+    }
+    
+    tracing::info!("Reset comparisons, removed {}", removed);
+    Json(ResetResponse { removed })
+}
+
+// Undo a whole bad session at once: delete every comparison made at or after
+// before_timestamp. With dry_run, only report how many would go.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/comparisons/rollback",
+    request_body = RollbackRequest,
+    responses(
+        (status = 200, body = RollbackResponse),
+        (status = 400, description = "Invalid timestamp", body = ErrorResponse),
+    ),
+))]
+async fn rollback_comparisons(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RollbackRequest>,
+) -> impl IntoResponse {
+    let Ok(cutoff) = chrono::DateTime::parse_from_rfc3339(&payload.before_timestamp) else {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "before_timestamp must be an RFC 3339 timestamp"
+        }))).into_response();
+    };
+    
+    let mut comparisons = state.comparisons.lock().unwrap();
+    // Timestamps are compared as instants, not strings, since offsets differ
+    let is_after = |c: &Comparison| {
+        chrono::DateTime::parse_from_rfc3339(&c.timestamp).is_ok_and(|t| t >= cutoff)
+    };
+    let removed = comparisons.iter().filter(|c| is_after(c)).count();
+    if !payload.dry_run {
+        comparisons.retain(|c| !is_after(c));
+        if removed > 0 {
+            state.comparisons_removed();
+        }
+        tracing::info!("Rolled back {} comparisons made since {}", removed, cutoff);
+    }
     
-    Json(RankingsResponse { rankings }).into_response()
-} 
\ No newline at end of file
+    Json(RollbackResponse { removed, dry_run: payload.dry_run }).into_response()
+}
+
+// Key grouping comparisons with the same outcome for the same pair, in
+// either order: (lower task id, higher task id, winner id)
+fn outcome_key(comp: &Comparison) -> (usize, usize, usize) {
+    (comp.task_a_id.min(comp.task_b_id), comp.task_a_id.max(comp.task_b_id), comp.winner_id)
+}
+
+// Outcomes recorded more than once for the same pair, e.g. by two people
+// answering the same question, which count double in the rankings
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/comparisons/duplicates",
+    responses((status = 200, body = Vec<DuplicateGroup>)),
+))]
+async fn find_duplicate_comparisons(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap();
+    let comparisons = state.comparisons.lock().unwrap();
+    
+    let mut groups: HashMap<(usize, usize, usize), Vec<&Comparison>> = HashMap::new();
+    for comp in comparisons.iter() {
+        groups.entry(outcome_key(comp)).or_default().push(comp);
+    }
+    
+    let content = |id: &usize| tasks.get(id).map(|t| t.content.clone()).unwrap_or_default();
+    let mut duplicates: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|(_, comps)| comps.len() > 1)
+        .map(|((a, b, winner), comps)| DuplicateGroup {
+            task_a_id: a,
+            task_b_id: b,
+            winner_id: winner,
+            task_a: content(&a),
+            task_b: content(&b),
+            winner: content(&winner),
+            count: comps.len(),
+            oldest_timestamp: comps.iter().map(|c| &c.timestamp).min().cloned().unwrap_or_default(),
+            newest_timestamp: comps.iter().map(|c| &c.timestamp).max().cloned().unwrap_or_default(),
+        })
+        .collect();
+    duplicates.sort_by(|x, y| y.count.cmp(&x.count).then((x.task_a_id, x.task_b_id).cmp(&(y.task_a_id, y.task_b_id))));
+    Json(duplicates)
+}
+
+// Keep only the oldest comparison of every duplicate group
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/comparisons/deduplicate",
+    responses((status = 200, body = DeduplicateResponse)),
+))]
+async fn deduplicate_comparisons(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut comparisons = state.comparisons.lock().unwrap();
+    let before = comparisons.len();
+    
+    // Comparisons are appended as they come in, so the first one seen is the oldest
+    let mut seen = HashSet::new();
+    comparisons.retain(|comp| seen.insert(outcome_key(comp)));
+    let removed = before - comparisons.len();
+    if removed > 0 {
+        state.comparisons_removed();
+    }
+    
+    tracing::info!("Removed {} duplicate comparisons", removed);
+    Json(DeduplicateResponse { removed })
+}
+
+// Seed the ranking from an existing manual order: tasks are matched by
+// content (and created if missing), then each one is recorded as beating
+// the next, i.e. n-1 comparisons marked `seeded`. That is enough for the
+// solver to start from the manual order while each link stays a single
+// comparison, so real answers that disagree soon outweigh it. If the seed
+// turns out to be mostly wrong, POST /comparisons/reset?seeded_only=true
+// removes it and keeps every real answer.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/import/ordered",
+    request_body = ImportOrderedRequest,
+    responses(
+        (status = 200, body = ImportResult),
+        (status = 400, description = "Task limit reached", body = ErrorResponse),
+    ),
+))]
+async fn import_ordered(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ImportOrderedRequest>,
+) -> impl IntoResponse {
+    let mut tasks = state.tasks.lock().unwrap();
+    let tag_priors = state.tag_priors.lock().unwrap();
+    let mut comparisons = state.comparisons.lock().unwrap();
+    
+    let mut content_to_id: HashMap<String, usize> = tasks
+        .values()
+        .map(|t| (t.content.clone(), t.id))
+        .collect();
+    
+    let new_contents: HashSet<&String> = payload
+        .ordered_contents
+        .iter()
+        .filter(|content| !content_to_id.contains_key(*content))
+        .collect();
+    if tasks.len() + new_contents.len() > state.max_tasks {
+        return too_many_tasks(state.max_tasks);
+    }
+    
+    let mut result = ImportResult { imported: 0, skipped: 0, errors: Vec::new() };
+    
+    // A repeated content keeps its first (highest) position
+    let mut seen = HashSet::new();
+    let mut ordered_ids = Vec::new();
+    for (pos, content) in payload.ordered_contents.iter().enumerate() {
+        if !seen.insert(content) {
+            result.skipped += 1;
+            result.errors.push(format!("item {}: {:?} is listed more than once", pos + 1, content));
+            continue;
+        }
+        let id = *content_to_id.entry(content.clone()).or_insert_with(|| {
+            let id = tasks.keys().max().map_or(1, |max_id| max_id + 1);
+            tasks.insert(id, TaskInfo {
+                id,
+                content: content.clone(),
+                completed: false,
+                line: 0,
+                file: String::new(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                anchor_score: None,
+                prior_win_rate: None,
+                initial_score: None,
+                tags: hashtags(content),
+                tag_prior: tag_prior(&hashtags(content), &tag_priors),
+            });
+            id
+        });
+        ordered_ids.push(id);
+    }
+    
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    for pair in ordered_ids.windows(2) {
+        comparisons.push(Comparison {
+            task_a_id: pair[0],
+            task_b_id: pair[1],
+            winner_id: pair[0],
+            timestamp: timestamp.clone(),
+            draw_margin: None,
+            seeded: true,
+        });
+        result.imported += 1;
+    }
+    
+    tracing::info!("Seeded {} comparisons from a manual order", result.imported);
+    Json(result).into_response()
+}
+
+// Import comparison history from the CLI's ratings.log. Tasks are matched by
+// content; any that don't exist yet are created.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/comparisons/import-ratings-log",
+    request_body = ImportRatingsLogRequest,
+    responses(
+        (status = 200, body = ImportResult),
+        (status = 400, description = "Task limit reached", body = ErrorResponse),
+    ),
+))]
+async fn import_ratings_log(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ImportRatingsLogRequest>,
+) -> impl IntoResponse {
+    let mut tasks = state.tasks.lock().unwrap();
+    let tag_priors = state.tag_priors.lock().unwrap();
+    let mut comparisons = state.comparisons.lock().unwrap();
+    
+    let mut content_to_id: HashMap<String, usize> = tasks
+        .values()
+        .map(|t| (t.content.clone(), t.id))
+        .collect();
+    
+    // Refuse up front if the tasks this would create don't fit, rather than
+    // stopping halfway through the log
+    let new_contents: HashSet<&String> = payload
+        .log_content
+        .lines()
+        .filter_map(|line| match line.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+            [winner_rid, loser_rid] => Some([
+                payload.id_to_content.get(winner_rid)?,
+                payload.id_to_content.get(loser_rid)?,
+            ]),
+            _ => None,
+        })
+        .flatten()
+        .filter(|content| !content_to_id.contains_key(*content))
+        .collect();
+    if tasks.len() + new_contents.len() > state.max_tasks {
+        return too_many_tasks(state.max_tasks);
+    }
+    
+    let mut result = ImportResult { imported: 0, skipped: 0, errors: Vec::new() };
+    
+    for (line_num, line) in payload.log_content.lines().enumerate() {
+        let line = line.trim();
+        // `# session` markers from the CLI's --max-per-session
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        
+        let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+        // Pairs the CLI skipped on timeout carry no preference
+        if let [_, _, "skip"] = parts[..] {
+            result.skipped += 1;
+            continue;
+        }
+        let [winner_rid, loser_rid] = parts[..] else {
+            result.skipped += 1;
+            result.errors.push(format!("line {}: expected `winner,loser`, got {:?}", line_num + 1, line));
+            continue;
+        };
+        
+        // Look up both sides before creating anything, so a line that is
+        // skipped doesn't leave a task behind
+        let content = |rid: &str| {
+            payload
+                .id_to_content
+                .get(rid)
+                .ok_or_else(|| format!("line {}: no content for rid {}", line_num + 1, rid))
+        };
+        let (winner, loser) = match (content(winner_rid), content(loser_rid)) {
+            (Ok(winner), Ok(loser)) => (winner, loser),
+            (Err(e), _) | (_, Err(e)) => {
+                result.skipped += 1;
+                result.errors.push(e);
+                continue;
+            }
+        };
+        
+        let mut task_id = |content: &String| -> usize {
+            if let Some(&id) = content_to_id.get(content) {
+                return id;
+            }
+            let id = tasks.keys().max().map_or(1, |max_id| max_id + 1);
+            tasks.insert(id, TaskInfo {
+                id,
+                content: content.clone(),
+                completed: false,
+                line: 0,
+                file: String::new(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                anchor_score: None,
+                prior_win_rate: None,
+                initial_score: None,
+                tags: hashtags(content),
+                tag_prior: tag_prior(&hashtags(content), &tag_priors),
+            });
+            content_to_id.insert(content.clone(), id);
+            id
+        };
+        let winner_id = task_id(winner);
+        let loser_id = task_id(loser);
+        comparisons.push(Comparison {
+            task_a_id: winner_id,
+            task_b_id: loser_id,
+            winner_id,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            draw_margin: None,
+            seeded: false,
+        });
+        result.imported += 1;
+    }
+    
+    Json(result).into_response()
+}
+
+// Run CPU-bound solver work on the blocking thread pool so it doesn't stall
+// the async workers. A panic inside the solver becomes a 500 response.
+async fn run_solver<T, F>(f: F) -> Result<T, axum::response::Response>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(|e| {
+        tracing::error!("Solver task failed: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "error": "Failed to compute rankings"
+        }))).into_response()
+    })
+}
+
+// Get rankings using the ASAP algorithm
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/rankings",
+    params(RankingsQuery),
+    responses(
+        (status = 200, body = RankingsResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_rankings(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RankingsQuery>,
+) -> impl IntoResponse {
+    // Snapshot the state so the locks aren't held while the solver runs
+    let mut tasks = state.tasks.lock().unwrap().clone();
+    let mut comparisons = state.comparisons.lock().unwrap().clone();
+    let mut dependencies = state.dependencies.lock().unwrap().clone();
+    
+    // With a tag filter the solver only sees the matching tasks and the
+    // comparisons among them, so scores are relative within the subset
+    if let Some(wanted) = tag_filter(query.tags.as_deref()) {
+        tasks.retain(|_, task| query.tags_match.matches(&task.tags, &wanted));
+        comparisons.retain(|c| tasks.contains_key(&c.task_a_id) && tasks.contains_key(&c.task_b_id));
+        dependencies.retain(|(a, b)| tasks.contains_key(a) && tasks.contains_key(b));
+    }
+    
+    // If we don't have enough tasks, return an empty response. Without any
+    // comparisons every task simply sits at the prior (score 0).
+    if tasks.len() < 2 {
+        return Json(RankingsResponse { rankings: Vec::new(), stats: ASAPStats::default(), next_pair: None, dependencies: Vec::new(), comparisons_heatmap: None }).into_response();
+    }
+    
+    let config = state.ranking_config;
+    let strategy = query.strategy;
+    let newest_first = query.newest_first;
+    let heatmap_k = query.include_heatmap.then(|| query.heatmap_k.unwrap_or(DEFAULT_HEATMAP_K));
+    match run_solver(move || {
+        compute_rankings(&tasks, &comparisons, config, strategy, newest_first, &dependencies, heatmap_k)
+    })
+    .await
+    {
+        Ok(mut response) => {
+            truncate_rankings(&mut response.rankings, query.top_k, query.bottom_k);
+            Json(response).into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+// Keep only the head and/or tail of an already ranked list
+fn truncate_rankings(rankings: &mut Vec<RankedTask>, top_k: Option<usize>, bottom_k: Option<usize>) {
+    let n = rankings.len();
+    match (top_k, bottom_k) {
+        (None, None) => {}
+        (Some(top), None) => rankings.truncate(top),
+        (None, Some(bottom)) => {
+            rankings.drain(..n.saturating_sub(bottom));
+        }
+        (Some(top), Some(bottom)) => {
+            // don't repeat tasks when the two ends overlap
+            let tail_start = n.saturating_sub(bottom).max(top.min(n));
+            rankings.drain(top.min(n)..tail_start);
+        }
+    }
+}
+
+// Current rank of each requested task content, in request order. Tasks that
+// don't exist or were never compared come back as null.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/tasks/batch-lookup",
+    request_body = BatchLookupRequest,
+    responses(
+        (status = 200, body = Vec<Option<RankedTask>>),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn batch_lookup_tasks(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<BatchLookupRequest>,
+) -> impl IntoResponse {
+    if payload.contents.len() > MAX_BATCH_LOOKUP {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("At most {} tasks can be looked up at once", MAX_BATCH_LOOKUP)
+        }))).into_response();
+    }
+    
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let dependencies = state.dependencies.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    let rankings = if tasks.len() < 2 {
+        Vec::new()
+    } else {
+        match run_solver(move || {
+            compute_rankings(&tasks, &comparisons, config, PairStrategy::Eig, false, &dependencies, None)
+        })
+        .await
+        {
+            Ok(response) => response.rankings,
+            Err(response) => return response,
+        }
+    };
+    
+    let mut by_content: HashMap<String, RankedTask> = HashMap::new();
+    for task in rankings.into_iter().filter(|t| t.direct_comparisons > 0) {
+        by_content.entry(task.content.clone()).or_insert(task);
+    }
+    let found: Vec<Option<&RankedTask>> = payload
+        .contents
+        .iter()
+        .map(|content| by_content.get(content))
+        .collect();
+    Json(found).into_response()
+}
+
+// Every task the given task has been compared against directly, with the
+// head-to-head record, most compared first
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/tasks/comparison-partners",
+    request_body = ComparisonPartnersRequest,
+    responses(
+        (status = 200, body = Vec<ComparisonPartner>),
+        (status = 404, body = ErrorResponse),
+    ),
+))]
+async fn get_comparison_partners(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ComparisonPartnersRequest>,
+) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap();
+    let comparisons = state.comparisons.lock().unwrap();
+    
+    // Duplicate tasks with the same content count as one
+    let ids: HashSet<usize> = tasks
+        .values()
+        .filter(|t| t.content == payload.content)
+        .map(|t| t.id)
+        .collect();
+    if ids.is_empty() {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "error": "Task not found"
+        }))).into_response();
+    }
+    
+    // partner id -> (wins, losses) from the requested task's side
+    let mut records: HashMap<usize, (u32, u32)> = HashMap::new();
+    for comp in comparisons.iter() {
+        let partner = match (ids.contains(&comp.task_a_id), ids.contains(&comp.task_b_id)) {
+            (true, false) => comp.task_b_id,
+            (false, true) => comp.task_a_id,
+            _ => continue,
+        };
+        let record = records.entry(partner).or_default();
+        if comp.winner_id == partner {
+            record.1 += 1;
+        } else {
+            record.0 += 1;
+        }
+    }
+    
+    let mut partners: Vec<ComparisonPartner> = records
+        .into_iter()
+        .filter_map(|(id, (wins, losses))| {
+            Some(ComparisonPartner {
+                id,
+                content: tasks.get(&id)?.content.clone(),
+                wins_against: wins,
+                losses_against: losses,
+                win_rate: wins as f64 / (wins + losses) as f64,
+            })
+        })
+        .collect();
+    partners.sort_by_key(|p| (std::cmp::Reverse(p.wins_against + p.losses_against), p.id));
+    Json(partners).into_response()
+}
+
+// Start tasks from win rates known from another ranking system, matched by
+// content. The next solve refines these priors with the comparisons.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/tasks/set-prior-batch",
+    request_body = SetPriorBatchRequest,
+    responses(
+        (status = 200, body = SetPriorBatchResponse),
+        (status = 400, description = "Win rate outside [0, 1]", body = ErrorResponse),
+    ),
+))]
+async fn set_prior_batch(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SetPriorBatchRequest>,
+) -> impl IntoResponse {
+    if let Some((content, _)) = payload.priors.iter().find(|(_, rate)| !(0.0..=1.0).contains(*rate)) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("Win rate for '{}' must be between 0 and 1", content)
+        }))).into_response();
+    }
+    
+    let mut tasks = state.tasks.lock().unwrap();
+    let mut updated = 0;
+    let mut matched = HashSet::new();
+    for task in tasks.values_mut() {
+        if let Some(&rate) = payload.priors.get(&task.content) {
+            task.prior_win_rate = Some(rate);
+            matched.insert(task.content.clone());
+            updated += 1;
+        }
+    }
+    let mut unknown: Vec<String> = payload
+        .priors
+        .into_keys()
+        .filter(|content| !matched.contains(content))
+        .collect();
+    unknown.sort_unstable();
+    
+    Json(SetPriorBatchResponse { updated, unknown }).into_response()
+}
+
+// Every tag used in a task, with how many tasks carry it and its prior
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/tags",
+    responses((status = 200, body = Vec<TagInfo>)),
+))]
+async fn get_tags(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap();
+    let tag_priors = state.tag_priors.lock().unwrap();
+    
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for tag in tasks.values().flat_map(|t| &t.tags) {
+        *counts.entry(tag).or_default() += 1;
+    }
+    let mut tags: Vec<TagInfo> = counts
+        .into_iter()
+        .map(|(tag, tasks)| TagInfo {
+            tag: tag.to_string(),
+            tasks,
+            prior_mean: tag_priors.get(tag).copied(),
+        })
+        .collect();
+    tags.sort_by(|a, b| b.tasks.cmp(&a.tasks).then_with(|| a.tag.cmp(&b.tag)));
+    Json(tags)
+}
+
+// Set the score tasks with a tag start from, so that e.g. all #work tasks
+// begin near each other until comparisons pull them apart. Tasks with
+// several tagged priors start from their mean.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/tags/priors",
+    request_body = SetTagPriorsRequest,
+    responses(
+        (status = 200, body = Vec<TagInfo>),
+        (status = 400, description = "Prior is out of range", body = ErrorResponse),
+    ),
+))]
+async fn set_tag_priors(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SetTagPriorsRequest>,
+) -> impl IntoResponse {
+    if let Some((tag, _)) = payload.priors.iter().find(|(_, mean)| mean.is_some_and(|m| !valid_score(m))) {
+        return invalid_score(&format!("Prior for '{}'", tag));
+    }
+    
+    {
+        let mut tasks = state.tasks.lock().unwrap();
+        let mut tag_priors = state.tag_priors.lock().unwrap();
+        for (tag, mean) in payload.priors {
+            let tag = tag.trim_start_matches('#').to_lowercase();
+            match mean {
+                Some(mean) => tag_priors.insert(tag, mean),
+                None => tag_priors.remove(&tag),
+            };
+        }
+        for task in tasks.values_mut() {
+            task.tag_prior = tag_prior(&task.tags, &tag_priors);
+        }
+    }
+    
+    get_tags(State(state)).await.into_response()
+}
+
+// Top-N rankings as a self-contained HTML fragment for embedding elsewhere
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/embed/rankings",
+    params(EmbedQuery),
+    responses(
+        (status = 200, description = "Self-contained HTML fragment", content_type = "text/html", body = String),
+        (status = 500, body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_embed_rankings(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EmbedQuery>,
+) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let dependencies = state.dependencies.lock().unwrap().clone();
+    let config = state.ranking_config;
+    let limit = query.limit.unwrap_or(DEFAULT_EMBED_LIMIT).min(MAX_EMBED_LIMIT);
+    
+    let items = if tasks.len() < 2 {
+        Vec::new()
+    } else {
+        match run_solver(move || {
+            compute_rankings(&tasks, &comparisons, config, PairStrategy::Eig, false, &dependencies, None)
+        })
+        .await
+        {
+            Ok(response) => response
+                .rankings
+                .into_iter()
+                .take(limit)
+                .map(|t| (t.content, t.score))
+                .collect(),
+            Err(response) => return response,
+        }
+    };
+    
+    let html = crate::embed::rankings_html(&items, matches!(query.theme, EmbedTheme::Dark));
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=300"),
+        ],
+        html,
+    )
+        .into_response()
+}
+
+// How many comparisons each task took part in, by task id
+fn comparison_counts(comparisons: &[Comparison]) -> HashMap<usize, usize> {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for comp in comparisons.iter() {
+        *counts.entry(comp.task_a_id).or_default() += 1;
+        *counts.entry(comp.task_b_id).or_default() += 1;
+    }
+    counts
+}
+
+// Win probability of every pair of tasks, with how uncertain it is, so a
+// close pair can be told apart from one that just needs more comparisons
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/probabilities",
+    params(ProbabilitiesQuery),
+    responses(
+        (status = 200, body = Vec<PairProbabilityInfo>),
+        (status = 400, description = "Invalid task_ids or too many tasks", body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_probabilities(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ProbabilitiesQuery>,
+) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    let mut ids: Vec<usize> = match &query.task_ids {
+        Some(list) => match list.split(',').map(|id| id.trim().parse()).collect() {
+            Ok(ids) => ids,
+            Err(_) => {
+                return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    "error": "task_ids must be comma-separated task ids"
+                }))).into_response();
+            }
+        },
+        None => tasks.keys().copied().collect(),
+    };
+    ids.sort_unstable();
+    ids.dedup();
+    if let Some(id) = ids.iter().find(|id| !tasks.contains_key(id)) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("Unknown task id {}", id)
+        }))).into_response();
+    }
+    if ids.len() > MAX_PROBABILITY_TASKS {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!(
+                "At most {} tasks at once, pick some with task_ids",
+                MAX_PROBABILITY_TASKS
+            )
+        }))).into_response();
+    }
+    
+    let pairs = run_solver(move || {
+        let (id_to_index, _) = comparison_matrix(&tasks, &comparisons);
+        let mut asap = new_asap(&tasks, &comparisons, &id_to_index, config);
+        asap.run_solver_on(&chronological_cmps(&comparisons, &id_to_index));
+        let mut pairs = Vec::new();
+        for (k, &a) in ids.iter().enumerate() {
+            for &b in &ids[k + 1..] {
+                let p = asap.pair_probability(id_to_index[&a], id_to_index[&b]);
+                pairs.push(PairProbabilityInfo {
+                    task_a_id: a,
+                    task_b_id: b,
+                    win_probability: p.win_probability,
+                    variance: p.variance,
+                    win_probability_low: p.low,
+                    win_probability_high: p.high,
+                });
+            }
+        }
+        pairs
+    })
+    .await;
+    
+    match pairs {
+        Ok(pairs) => Json(pairs).into_response(),
+        Err(response) => response,
+    }
+}
+
+// How many comparisons disagree with the solved order, i.e. the winner ended
+// up scored below the loser. A high rate means noisy answers or real cycles.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/consistency",
+    responses(
+        (status = 200, body = ConsistencyResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_consistency(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    let result = run_solver(move || {
+        let (id_to_index, _) = comparison_matrix(&tasks, &comparisons);
+        let cmps = chronological_cmps(&comparisons, &id_to_index);
+        let mut asap = new_asap(&tasks, &comparisons, &id_to_index, config);
+        let (_, ms, _) = asap.run_solver_on(&cmps);
+        let contradictions = cmps.iter().filter(|&&[winner, loser]| ms[winner] < ms[loser]).count();
+        ConsistencyResponse {
+            comparisons: cmps.len(),
+            contradictions,
+            contradiction_rate: if cmps.is_empty() { 0.0 } else { contradictions as f64 / cmps.len() as f64 },
+        }
+    })
+    .await;
+    
+    match result {
+        Ok(response) => Json(response).into_response(),
+        Err(response) => response,
+    }
+}
+
+// Head-to-head record of two tasks, by content, next to what the model
+// predicts for them; one cell of a comparison grid
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post, path = "/pair",
+    request_body = PairRequest,
+    responses(
+        (status = 200, body = PairResponse),
+        (status = 400, description = "Both sides are the same task", body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_pair(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<PairRequest>,
+) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    if payload.task_a_content == payload.task_b_content {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "task_a_content and task_b_content are the same task"
+        }))).into_response();
+    }
+    
+    // Duplicate tasks with the same content count as one
+    let ids_of = |content: &str| -> HashSet<usize> {
+        tasks.values().filter(|t| t.content == content).map(|t| t.id).collect()
+    };
+    let (a_ids, b_ids) = (ids_of(&payload.task_a_content), ids_of(&payload.task_b_content));
+    let (Some(&a), Some(&b)) = (a_ids.iter().min(), b_ids.iter().min()) else {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "error": "Task not found"
+        }))).into_response();
+    };
+    
+    let (mut a_wins, mut b_wins) = (0, 0);
+    for comp in &comparisons {
+        let between = (a_ids.contains(&comp.task_a_id) && b_ids.contains(&comp.task_b_id))
+            || (b_ids.contains(&comp.task_a_id) && a_ids.contains(&comp.task_b_id));
+        if !between {
+            continue;
+        }
+        if a_ids.contains(&comp.winner_id) {
+            a_wins += 1;
+        } else {
+            b_wins += 1;
+        }
+    }
+    
+    let model_probability = run_solver(move || {
+        let (id_to_index, _) = comparison_matrix(&tasks, &comparisons);
+        let mut asap = new_asap(&tasks, &comparisons, &id_to_index, config);
+        asap.run_solver_on(&chronological_cmps(&comparisons, &id_to_index));
+        asap.pair_probability(id_to_index[&a], id_to_index[&b]).win_probability
+    })
+    .await;
+    
+    match model_probability {
+        Ok(model_probability) => Json(PairResponse {
+            comparisons: a_wins + b_wins,
+            a_wins,
+            b_wins,
+            model_probability,
+        })
+        .into_response(),
+        Err(response) => response,
+    }
+}
+
+// The confidence interval plot the CLI writes to ratings_ci.html, rendered
+// in memory from the current comparisons
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/rankings/svg",
+    params(SvgQuery),
+    responses(
+        (status = 200, description = "Confidence interval plot", content_type = "image/svg+xml", body = String),
+        (status = 500, body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_rankings_svg(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SvgQuery>,
+) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let config = state.ranking_config;
+    let options = PlotOptions { dark_mode: query.dark_mode };
+    
+    let svg = run_solver(move || {
+        let (id_to_index, _) = comparison_matrix(&tasks, &comparisons);
+        let mut asap = new_asap(&tasks, &comparisons, &id_to_index, config);
+        let (_, ms, vs) = asap.run_solver_on(&chronological_cmps(&comparisons, &id_to_index));
+        let counts = comparison_counts(&comparisons);
+        let items = id_to_index
+            .iter()
+            .map(|(id, &i)| {
+                let count = counts.get(id).copied().unwrap_or(0);
+                (tasks[id].content.clone(), ms[i], vs[i], count)
+            })
+            .collect();
+        let mut buf = Vec::new();
+        plot_ci_with_options(items, &options, &mut buf).map(|_| buf)
+    })
+    .await;
+    
+    match svg {
+        Err(response) => response,
+        Ok(Ok(bytes)) => (
+            [
+                (header::CONTENT_TYPE, "image/svg+xml"),
+                (header::CACHE_CONTROL, "public, max-age=60"),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to render rankings SVG: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "error": "Failed to render SVG"
+            }))).into_response()
+        }
+    }
+}
+
+// Export the current rankings as a printable PDF
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get, path = "/rankings/pdf-export",
+    responses(
+        (status = 200, description = "Rankings as a PDF document", content_type = "application/pdf", body = Vec<u8>),
+        (status = 500, body = ErrorResponse),
+        (status = 503, description = "Request timed out", body = ErrorResponse),
+    ),
+))]
+async fn get_rankings_pdf(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let tasks = state.tasks.lock().unwrap().clone();
+    let comparisons = state.comparisons.lock().unwrap().clone();
+    let dependencies = state.dependencies.lock().unwrap().clone();
+    let config = state.ranking_config;
+    
+    let pdf = run_solver(move || {
+        let rankings = if tasks.len() < 2 {
+            Vec::new()
+        } else {
+            compute_rankings(&tasks, &comparisons, config, PairStrategy::Eig, false, &dependencies, None).rankings
+        };
+        
+        let counts = comparison_counts(&comparisons);
+        let rows: Vec<_> = rankings
+            .iter()
+            .map(|t| (t.rank, t.content.clone(), t.score, counts.get(&t.id).copied().unwrap_or(0)))
+            .collect();
+        let title = format!(
+            "Task rankings ({})",
+            chrono::Utc::now().format("%Y-%m-%d %H:%M UTC")
+        );
+        let footer = format!(
+            "Ranked with ASAP (TrueSkill) from {} comparisons across {} tasks",
+            comparisons.len(),
+            tasks.len()
+        );
+        crate::pdf_export::rankings_pdf(&title, &rows, &footer)
+    })
+    .await;
+    
+    match pdf {
+        Err(response) => response,
+        Ok(Ok(bytes)) => (
+            [
+                (header::CONTENT_TYPE, "application/pdf"),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"rankings.pdf\""),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to generate rankings PDF: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "error": "Failed to generate PDF"
+            }))).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_cutoff_overflow_is_none() {
+        let now = chrono::Utc::now();
+        assert_eq!(prune_cutoff(now, 30), Some(now - chrono::TimeDelta::days(30)));
+        assert_eq!(prune_cutoff(now, 0), Some(now));
+        assert_eq!(prune_cutoff(now, u32::MAX), None);
+        assert_eq!(prune_cutoff(now, 4_000_000_000), None);
+    }
+
+    #[test]
+    fn depends_on_follows_chains() {
+        let dependencies = [(1, 2), (2, 3), (4, 3)];
+        assert!(depends_on(&dependencies, 2, 1));
+        assert!(depends_on(&dependencies, 3, 1));
+        assert!(depends_on(&dependencies, 3, 4));
+        assert!(!depends_on(&dependencies, 1, 3));
+        assert!(!depends_on(&dependencies, 4, 1));
+        assert!(!depends_on(&dependencies, 1, 5));
+    }
+
+    #[test]
+    fn empty_tag_filter_is_no_filter() {
+        for filter in [None, Some(""), Some(","), Some(" , # ")] {
+            assert_eq!(tag_filter(filter), None, "{:?}", filter);
+        }
+        let wanted = tag_filter(Some("#Work, home,")).unwrap();
+        assert_eq!(wanted, ["work", "home"]);
+
+        let tags = |t: &[&str]| -> Vec<String> { t.iter().map(|s| s.to_string()).collect() };
+        assert!(TagMatch::Any.matches(&tags(&["work"]), &wanted));
+        assert!(!TagMatch::All.matches(&tags(&["work"]), &wanted));
+        assert!(TagMatch::All.matches(&tags(&["home", "errand", "work"]), &wanted));
+        assert!(!TagMatch::Any.matches(&tags(&["errand"]), &wanted));
+    }
+}
@@ -0,0 +1,97 @@
+// Chat notifications for when a list has converged, i.e. more comparisons
+// are unlikely to change the order. Slack and Discord use incoming webhooks,
+// configured through SLACK_WEBHOOK_URL and DISCORD_WEBHOOK_URL.
+
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    slack_url: Option<String>,
+    discord_url: Option<String>,
+    // How the list is named in the message
+    list_name: String,
+    // Where the web UI is served, linked from the message when set
+    web_ui_url: Option<String>,
+}
+
+impl WebhookConfig {
+    /// Read the webhook settings from the environment. `None` when neither
+    /// webhook URL is set.
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|s: &String| !s.is_empty());
+        let config = WebhookConfig {
+            slack_url: var("SLACK_WEBHOOK_URL"),
+            discord_url: var("DISCORD_WEBHOOK_URL"),
+            list_name: var("LIST_NAME").unwrap_or_else(|| "todo-sorter".to_string()),
+            web_ui_url: var("WEB_UI_URL"),
+        };
+        (config.slack_url.is_some() || config.discord_url.is_some()).then_some(config)
+    }
+}
+
+fn summary(list_name: &str, score: f64) -> String {
+    format!("{} has converged (rank stability {:.3}), you can stop comparing", list_name, score)
+}
+
+fn numbered(top: &[(String, f64)]) -> String {
+    top.iter()
+        .enumerate()
+        .map(|(i, (content, _))| format!("{}. {}", i + 1, content))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Slack message with the convergence score and the `top` tasks, best first.
+pub fn slack_payload(config: &WebhookConfig, score: f64, top: &[(String, f64)]) -> Value {
+    let mut blocks = vec![
+        json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": format!("*{}*", summary(&config.list_name, score)) },
+        }),
+        json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": numbered(top) },
+        }),
+    ];
+    if let Some(url) = &config.web_ui_url {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": format!("<{}|Open the rankings>", url) }],
+        }));
+    }
+    json!({ "text": summary(&config.list_name, score), "blocks": blocks })
+}
+
+/// Discord message with the same content as `slack_payload`, as an embed.
+pub fn discord_payload(config: &WebhookConfig, score: f64, top: &[(String, f64)]) -> Value {
+    let mut embed = json!({
+        "title": format!("{} has converged", config.list_name),
+        "description": numbered(top),
+        "color": 0x2563eb,
+        "fields": [{ "name": "Rank stability", "value": format!("{:.3}", score), "inline": true }],
+    });
+    if let Some(url) = &config.web_ui_url {
+        embed["url"] = json!(url);
+    }
+    json!({ "embeds": [embed] })
+}
+
+/// Post the convergence message to every configured webhook. Failures are
+/// logged rather than returned; nobody is waiting on the result.
+pub async fn notify_converged(config: &WebhookConfig, score: f64, top: &[(String, f64)]) {
+    let client = reqwest::Client::new();
+    let targets = [
+        (config.slack_url.as_ref(), slack_payload(config, score, top)),
+        (config.discord_url.as_ref(), discord_payload(config, score, top)),
+    ];
+    for (url, payload) in targets {
+        let Some(url) = url else { continue };
+        match client.post(url).json(&payload).send().await {
+            Ok(response) if !response.status().is_success() => {
+                tracing::warn!("Webhook returned {}", response.status());
+            }
+            Ok(_) => tracing::info!("Sent convergence notification"),
+            Err(e) => tracing::warn!("Failed to send webhook: {}", e),
+        }
+    }
+}